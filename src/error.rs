@@ -0,0 +1,176 @@
+use std::fmt::Display;
+use std::io::{Error, ErrorKind};
+
+use crate::interpreter::{Data, InterpreterError};
+use crate::lexer::{LexError, TokenLocation};
+use crate::parser::ParseError;
+
+/// An error raised while lexing, parsing or interpreting a program.
+#[derive(Debug)]
+pub enum MathyError {
+    Lex(LexError),
+    Parse(ParseError),
+    Interpreter(InterpreterError),
+}
+
+impl Display for MathyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lex(err) => write!(f, "{}", err),
+            Self::Parse(err) => write!(f, "{}", err),
+            Self::Interpreter(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MathyError {}
+
+impl MathyError {
+    /// Returns where the error occurred, if known.
+    pub fn location(&self) -> Option<&TokenLocation> {
+        match self {
+            Self::Lex(err) => err.location(),
+            Self::Parse(err) => err.location(),
+            Self::Interpreter(err) => err.location(),
+        }
+    }
+
+    /// Returns the stage of the pipeline the error occurred in.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Lex(_) => "lex",
+            Self::Parse(_) => "parse",
+            Self::Interpreter(_) => "runtime",
+        }
+    }
+
+    /// Serializes the error as a JSON object with the fields `file`, `line`, `col`, `kind` and
+    /// `message`. `file_path` is used as the file if the location of the error is unknown, in
+    /// which case `line` and `col` are `null`.
+    pub fn to_json(&self, file_path: &str) -> String {
+        let (file, line, col) = match self.location() {
+            Some(TokenLocation(file, col, row)) => {
+                (file.as_str(), row.to_string(), col.to_string())
+            }
+            None => (file_path, "null".to_string(), "null".to_string()),
+        };
+
+        format!(
+            "{{\"file\":{},\"line\":{},\"col\":{},\"kind\":\"{}\",\"message\":{}}}",
+            Data::Str(file.to_string()).to_json(),
+            line,
+            col,
+            self.kind(),
+            Data::Str(self.to_string()).to_json()
+        )
+    }
+}
+
+impl From<LexError> for MathyError {
+    fn from(value: LexError) -> Self {
+        Self::Lex(value)
+    }
+}
+
+impl From<ParseError> for MathyError {
+    fn from(value: ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<InterpreterError> for MathyError {
+    fn from(value: InterpreterError) -> Self {
+        Self::Interpreter(value)
+    }
+}
+
+impl From<MathyError> for Error {
+    fn from(value: MathyError) -> Error {
+        let kind = match value {
+            MathyError::Lex(LexError::UnexpectedEof) | MathyError::Parse(ParseError::EOF) => {
+                ErrorKind::UnexpectedEof
+            }
+            _ => ErrorKind::Other,
+        };
+
+        Error::new(kind, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_errors() {
+        assert!(matches!(
+            crate::tokenize("x = $"),
+            Err(MathyError::Lex(LexError::UnexpectedCharacter('$', _)))
+        ));
+        assert!(matches!(
+            crate::tokenize("print \"abc"),
+            Err(MathyError::Lex(LexError::UnterminatedString(_)))
+        ));
+        assert!(matches!(
+            crate::tokenize("#{ never closed"),
+            Err(MathyError::Lex(LexError::UnterminatedBlockComment(_)))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            crate::parse("[1, 2"),
+            Err(MathyError::Parse(ParseError::Expected(expected, TokenLocation(_, 1, 1))))
+                if expected == "]"
+        ));
+        assert!(matches!(
+            crate::parse("x = )"),
+            Err(MathyError::Parse(ParseError::UnexpectedToken(..)))
+        ));
+    }
+
+    #[test]
+    fn runtime_errors() {
+        match crate::eval("y + 1") {
+            Err(MathyError::Interpreter(err)) => assert!(matches!(
+                err.inner(),
+                InterpreterError::UndefinedVariable(name) if name == "y"
+            )),
+            out => panic!("expected a runtime error, got {:?}", out),
+        }
+        match crate::eval("nope(1)") {
+            Err(MathyError::Interpreter(err)) => assert!(matches!(
+                err.inner(),
+                InterpreterError::UndefinedFunction(name) if name == "nope"
+            )),
+            out => panic!("expected a runtime error, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn io_error_kinds() {
+        let err: Error = MathyError::Parse(ParseError::EOF).into();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let err: Error = MathyError::Interpreter(InterpreterError::ExpectedList).into();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(err.to_string(), "Expected list!");
+    }
+
+    #[test]
+    fn json_fields() {
+        let err = crate::tokenize("x = $").unwrap_err();
+        assert_eq!(err.kind(), "lex");
+        assert_eq!(
+            err.to_json("ignored.mth"),
+            "{\"file\":\"<input>\",\"line\":1,\"col\":5,\"kind\":\"lex\",\
+             \"message\":\"Unexpected character '$' at <input>:1:5\"}"
+        );
+        let err = MathyError::Interpreter(InterpreterError::SingularMatrix);
+        assert_eq!(
+            err.to_json("a.mth"),
+            "{\"file\":\"a.mth\",\"line\":null,\"col\":null,\"kind\":\"runtime\",\
+             \"message\":\"Matrix is singular!\"}"
+        );
+    }
+}
@@ -1,64 +1,235 @@
-use crate::lexer::{Token, TokenType};
-use crate::parser::{Expr, Operator, Parsed};
-use crate::util::error;
+use crate::error::MathyError;
+use crate::lexer::{Lexer, Token, TokenLocation, TokenType};
+use crate::parser::{Expr, Operator, ParamType, Parameter, Parsed, Parser};
 
+use std::cell::Cell;
 use std::f64::consts::PI;
 use std::fmt::Display;
+use std::fs::{canonicalize, read_to_string};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{
     collections::HashMap,
-    io::{Error, ErrorKind, Result},
+    io::{stdout, Write},
 };
 
 #[derive(Debug)]
-enum InterpreterError {
+pub enum InterpreterError {
     UndefinedVariable(String),
     UndefinedFunction(String),
     InvalidArguments(String),
+    /// An argument that doesn't match the annotation of its parameter, with the names of the
+    /// function and parameter.
+    ArgumentType(String, String, ParamType, Data),
     InvalidListLength,
+    /// A nested list whose rows differ in length or mix numbers and lists.
+    RaggedList,
+    IterationLimit(String),
+    SingularMatrix,
+    RecursionLimit(String),
+    StepLimitExceeded(usize),
+    InvalidOperands(Operator),
+    ExpectedNumber(String),
+    ExpectedList,
+    ExpectedIdent,
+    RedeclaredVariable(String, Option<TokenLocation>),
+    RedeclaredFunction(String, TokenLocation),
+    InvalidLoopBounds,
+    UnreachableLoopEnd(f64, f64, f64),
+    InvalidForStep,
+    InvalidDestructuring,
+    DestructuringLength(usize, usize),
+    InvalidDestructuringTarget,
+    IndexOutOfRange(f64, usize),
+    UnexpectedLambda,
+    DomainError(String, f64),
+    NonFinite(f64),
+    AssertionFailed(TokenLocation, Option<String>),
+    ImportRead(String, std::io::Error),
+    ImportCycle(String),
+    /// A lexing or parsing error in an imported file.
+    Import(Box<MathyError>),
+    Io(std::io::Error),
+    /// An error without a location of its own, along with the statement it occurred in.
+    At(Box<InterpreterError>, TokenLocation),
 }
 
-impl From<InterpreterError> for Error {
-    fn from(value: InterpreterError) -> Error {
-        match value {
-            InterpreterError::UndefinedVariable(name) => {
-                error!(Other, "Undefined variable: {:?}", name)
+impl Display for InterpreterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UndefinedVariable(name) => write!(f, "Undefined variable: {:?}", name),
+            Self::UndefinedFunction(name) => write!(f, "Undefined function: {:?}", name),
+            Self::InvalidListLength => write!(f, "Lists must be same length!"),
+            Self::RaggedList => write!(f, "Rows of a nested list must have the same shape!"),
+            Self::InvalidArguments(name) => {
+                write!(f, "Invalid arguments for function {:?}!", name)
+            }
+            Self::ArgumentType(function, parameter, annotation, got) => {
+                // Strings are quoted so that they can't be mistaken for names.
+                let got = match got {
+                    Data::Str(value) => format!("{:?}", value),
+                    got => got.to_string(),
+                };
+                write!(
+                    f,
+                    "Expected argument {:?} of function {:?} to be a {}, got {}!",
+                    parameter, function, annotation, got
+                )
+            }
+            Self::SingularMatrix => write!(f, "Matrix is singular!"),
+            Self::InvalidOperands(op) => {
+                write!(f, "Invalid operands for operator {:?}!", op.to_string())
+            }
+            Self::ExpectedNumber(got) => write!(f, "Expected a number, got {:?}!", got),
+            Self::RecursionLimit(name) => {
+                write!(f, "Recursion limit reached in function {:?}!", name)
+            }
+            Self::StepLimitExceeded(limit) => {
+                write!(f, "Step limit of {} exceeded!", limit)
             }
-            InterpreterError::UndefinedFunction(name) => {
-                error!(Other, "Undefined function: {:?}", name)
+            Self::IterationLimit(name) => {
+                write!(f, "Iteration limit reached in function {:?}!", name)
             }
-            InterpreterError::InvalidListLength => error!(Other, "Lists must be same length!"),
-            InterpreterError::InvalidArguments(name) => {
-                error!(Other, "Invalid arguments for function {:?}!", name)
+            Self::ExpectedList => write!(f, "Expected list!"),
+            Self::ExpectedIdent => write!(f, "Expected identefier!"),
+            Self::RedeclaredVariable(name, Some(loc)) => {
+                write!(f, "Re-decleration of variable {:?} at {}", name, loc)
             }
+            Self::RedeclaredVariable(name, None) => {
+                write!(f, "Re-decleration of variable {:?}", name)
+            }
+            Self::RedeclaredFunction(name, loc) => {
+                write!(f, "Re-decleration of function {:?} at {}", name, loc)
+            }
+            Self::InvalidLoopBounds => write!(f, "From-to-as-loop can only contain numbers"),
+            Self::UnreachableLoopEnd(step, max, min) => write!(
+                f,
+                "From-to-as-loop with step {} can never reach {} from {}",
+                step, max, min
+            ),
+            Self::InvalidForStep => {
+                write!(f, "Step of for-in-loop must be a positive whole number")
+            }
+            Self::InvalidDestructuring => write!(f, "Some error!"),
+            Self::DestructuringLength(values, names) => write!(
+                f,
+                "Cannot destructure a list of {} values into {} names!",
+                values, names
+            ),
+            Self::InvalidDestructuringTarget => write!(f, "Only idents allowed in destructor!"),
+            Self::IndexOutOfRange(index, len) => write!(
+                f,
+                "Index {} is out of range for a list of length {}!",
+                index, len
+            ),
+            Self::UnexpectedLambda => {
+                write!(f, "Lambdas can only be passed to functions like map!")
+            }
+            Self::DomainError(name, value) => {
+                write!(f, "{} is outside the domain of function {:?}!", value, name)
+            }
+            Self::NonFinite(value) => {
+                write!(
+                    f,
+                    "Operation produced a non-finite result: {}",
+                    float_source(*value)
+                )
+            }
+            Self::AssertionFailed(loc, None) => write!(f, "Assertion failed at {}", loc),
+            Self::AssertionFailed(loc, Some(message)) => {
+                write!(f, "Assertion failed at {}: {}", loc, message)
+            }
+            Self::ImportRead(path, err) => write!(f, "Could not import {:?}: {}", path, err),
+            Self::ImportCycle(path) => write!(f, "Cyclic import of {:?}!", path),
+            Self::Import(err) => write!(f, "{}", err),
+            Self::Io(err) => write!(f, "{}", err),
+            Self::At(err, loc) => write!(f, "{} at {}", err.to_string().trim_end_matches('!'), loc),
         }
     }
 }
 
-impl Into<Expr> for Data {
-    fn into(self) -> Expr {
+impl std::error::Error for InterpreterError {}
+
+impl InterpreterError {
+    /// Returns where the error occurred, if known.
+    pub fn location(&self) -> Option<&TokenLocation> {
         match self {
+            Self::RedeclaredVariable(_, loc) => loc.as_ref(),
+            Self::RedeclaredFunction(_, loc) | Self::AssertionFailed(loc, _) => Some(loc),
+            Self::Import(err) => err.location(),
+            Self::At(_, loc) => Some(loc),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the statement location added by [`InterpreterError::At`].
+    pub fn inner(&self) -> &InterpreterError {
+        match self {
+            Self::At(err, _) => err.inner(),
+            err => err,
+        }
+    }
+
+    /// Attaches `loc` to the error, unless it already has a location.
+    fn at(self, loc: Option<&TokenLocation>) -> Self {
+        match loc {
+            Some(loc) if self.location().is_none() => Self::At(Box::new(self), loc.clone()),
+            _ => self,
+        }
+    }
+}
+
+impl From<std::io::Error> for InterpreterError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Data> for Expr {
+    fn from(value: Data) -> Self {
+        match value {
             Data::Float(value) => Expr::from(value),
             Data::List(values) => Expr::List(values.into_iter().map(|data| data.into()).collect()),
+            Data::Str(value) => Expr::StringLiteral(value),
         }
     }
 }
 
+impl From<bool> for Data {
+    /// Truth values are represented as 1 and 0.
+    fn from(value: bool) -> Self {
+        Self::Float(if value { 1.0 } else { 0.0 })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Data {
     Float(f64),
     List(Vec<Data>),
+    Str(String),
+}
+
+/// Formats `value`, printing NaN as `nan` to match the literal.
+fn float_source(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else {
+        value.to_string()
+    }
 }
 
 impl Display for Data {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Float(value) => write!(f, "{}", value)?,
+            Self::Float(value) => write!(f, "{}", float_source(*value))?,
+            Self::Str(value) => write!(f, "{}", value)?,
             Self::List(datas) => {
                 let mut buf = String::from("[");
                 for (i, data) in datas.iter().enumerate() {
                     match data {
-                        Data::Float(value) => buf.push_str(value.to_string().as_str()),
+                        Data::Float(value) => buf.push_str(&float_source(*value)),
                         Data::List(_) => buf.push_str(data.to_string().as_str()),
+                        Data::Str(value) => buf.push_str(format!("{:?}", value).as_str()),
                     };
                     if i != datas.len() - 1 {
                         buf.push_str(", ");
@@ -74,48 +245,118 @@ impl Display for Data {
     }
 }
 
+impl Data {
+    /// Rounds every number to at most `precision` decimal places.
+    pub fn rounded(&self, precision: usize) -> Data {
+        match self {
+            Self::Float(value) if value.is_finite() => {
+                let rounded: f64 = format!("{:.*}", precision, value).parse().unwrap_or(*value);
+                // Small negative numbers shouldn't print as `-0`.
+                Self::Float(if rounded == 0.0 { 0.0 } else { rounded })
+            }
+            Self::List(datas) => {
+                Self::List(datas.iter().map(|data| data.rounded(precision)).collect())
+            }
+            data => data.clone(),
+        }
+    }
+
+    /// Serializes the value as JSON. Non-finite floats have no JSON representation and become
+    /// `null`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::Float(value) if value.is_finite() => value.to_string(),
+            Self::Float(_) => "null".to_string(),
+            Self::List(datas) => {
+                let values: Vec<String> = datas.iter().map(|data| data.to_json()).collect();
+                format!("[{}]", values.join(","))
+            }
+            Self::Str(value) => {
+                let mut buf = String::from("\"");
+                for c in value.chars() {
+                    match c {
+                        '"' => buf.push_str("\\\""),
+                        '\\' => buf.push_str("\\\\"),
+                        '\n' => buf.push_str("\\n"),
+                        '\t' => buf.push_str("\\t"),
+                        '\r' => buf.push_str("\\r"),
+                        c if c.is_control() => buf.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => buf.push(c),
+                    }
+                }
+                buf.push('"');
+                buf
+            }
+        }
+    }
+}
+
+/// How printed values are formatted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Uses the [`Display`] implementation of [`Data`].
+    Plain,
+    /// Uses [`Data::to_json`].
+    Json,
+}
+
 fn apply_op(left: Data, right: Data, op: Operator) -> InterpreterResult<Data> {
+    if let Operator::In = op {
+        // Only the top level of the list is searched, like `contains`.
+        let Data::List(values) = right else {
+            return Err(InterpreterError::InvalidOperands(op));
+        };
+        return Ok(Data::from(values.contains(&left)));
+    }
+
+    if matches!(left, Data::Str(_)) || matches!(right, Data::Str(_)) {
+        // Adding a string to any value concatenates their printed forms.
+        return match op {
+            Operator::Plus => Ok(Data::Str(format!("{}{}", left, right))),
+            Operator::Equal => Ok(Data::from(left == right)),
+            Operator::NotEqual => Ok(Data::from(left != right)),
+            _ => Err(InterpreterError::InvalidOperands(op)),
+        };
+    }
+
     if let Data::List(ref values1) = left {
         if let Data::List(ref values2) = right {
             if values1.len() != values2.len() {
                 return Err(InterpreterError::InvalidListLength);
             }
+            shape(&left)?;
+            shape(&right)?;
 
             return Ok(Data::List(
                 values1
                     .iter()
                     .zip(values2)
                     .map(|(value1, value2)| apply_op(value1.clone(), value2.clone(), op.clone()))
-                    .map(|res| res.unwrap_or_else(|err| panic!("Error: {}", Error::from(err))))
-                    .collect(),
+                    .collect::<InterpreterResult<Vec<Data>>>()?,
             ));
         }
     };
     let left_val = match left {
         Data::Float(value1) => value1,
+        Data::Str(_) => unreachable!("Internal error!"),
         Data::List(values) => {
             return Ok(Data::List(
                 values
                     .iter()
-                    .map(|data| {
-                        apply_op(data.clone(), right.clone(), op.clone())
-                            .unwrap_or_else(|err| panic!("{:?}", err))
-                    })
-                    .collect(),
+                    .map(|data| apply_op(data.clone(), right.clone(), op.clone()))
+                    .collect::<InterpreterResult<Vec<Data>>>()?,
             ))
         }
     };
     let right_val = match right {
         Data::Float(value1) => value1,
+        Data::Str(_) => unreachable!("Internal error!"),
         Data::List(values) => {
             return Ok(Data::List(
                 values
                     .iter()
-                    .map(|data| {
-                        apply_op(left.clone(), data.clone(), op.clone())
-                            .unwrap_or_else(|err| panic!("{:?}", err))
-                    })
-                    .collect(),
+                    .map(|data| apply_op(left.clone(), data.clone(), op.clone()))
+                    .collect::<InterpreterResult<Vec<Data>>>()?,
             ))
         }
     };
@@ -125,30 +366,431 @@ fn apply_op(left: Data, right: Data, op: Operator) -> InterpreterResult<Data> {
         Operator::Minus => Data::Float(left_val - right_val),
         Operator::Multi => Data::Float(left_val * right_val),
         Operator::Div => Data::Float(left_val / right_val),
+        Operator::FloorDiv => Data::Float((left_val / right_val).floor()),
         Operator::Pow => Data::Float(left_val.powf(right_val)),
+        Operator::Less => Data::from(left_val < right_val),
+        Operator::LessEqual => Data::from(left_val <= right_val),
+        Operator::Greater => Data::from(left_val > right_val),
+        Operator::GreaterEqual => Data::from(left_val >= right_val),
+        Operator::Equal => Data::from(left_val == right_val),
+        Operator::NotEqual => Data::from(left_val != right_val),
+        // Bitwise operators truncate their operands to integers.
+        Operator::BitAnd => Data::Float((left_val as i64 & right_val as i64) as f64),
+        Operator::BitOr => Data::Float((left_val as i64 | right_val as i64) as f64),
+        Operator::Xor => Data::Float((left_val as i64 ^ right_val as i64) as f64),
+        Operator::In => unreachable!("Handled above"),
+        Operator::ShiftLeft | Operator::ShiftRight => {
+            // Shifting by a negative amount, or by the width of an integer or more, is an error.
+            if !(0.0..64.0).contains(&right_val.trunc()) {
+                return Err(InterpreterError::InvalidOperands(op));
+            }
+            let (value, amount) = (left_val as i64, right_val as u32);
+            Data::Float(match op {
+                Operator::ShiftLeft => (value << amount) as f64,
+                _ => (value >> amount) as f64,
+            })
+        }
     })
 }
 
-fn apply_func(data: Data, func: fn(f64) -> Data) -> Data {
+/// Returns whether `data` counts as true in a condition, which is any number but 0.
+fn is_truthy(data: Data) -> InterpreterResult<bool> {
+    match data {
+        Data::Float(value) => Ok(value != 0.0),
+        data => Err(InterpreterError::ExpectedNumber(data.to_string())),
+    }
+}
+
+/// The names of all built-in functions.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "sin",
+    "cos",
+    "tan",
+    "concat",
+    "collatz",
+    "zip",
+    "dot",
+    "matrix_solve",
+    "clamp",
+    "len",
+    "sum",
+    "mean",
+    "median",
+    "stddev",
+    "exp",
+    "pow",
+    "asin",
+    "acos",
+    "atan",
+    "atan2",
+    "sinh",
+    "cosh",
+    "tanh",
+    "sign",
+    "trunc",
+    "first",
+    "last",
+    "tail",
+    "contains",
+    "flatten",
+    "slice",
+    "round",
+    "gcd",
+    "lcm",
+    "factorial",
+    "count",
+    "index_of",
+    "map",
+    "filter",
+    "reduce",
+    "defined",
+    "equals",
+    "fill",
+    "linspace",
+    "sqrt",
+    "ln",
+    "transpose",
+    "mod",
+    "random",
+    "seed",
+    "randint",
+    "shuffle",
+    "cumsum",
+    "cumprod",
+];
+
+/// The names of all built-in constants.
+const CONSTANTS: &[&str] = &["PI", "TAU", "GLR", "inf", "nan"];
+
+/// Turns `seed` into a non-zero state for the xorshift generator, using the SplitMix64 mixing
+/// function so that nearby seeds give unrelated sequences.
+fn rng_state(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+/// The maximum number of iterations a generating built-in may run for.
+const ITERATION_LIMIT: usize = 100_000;
+
+/// Generates the Collatz sequence starting at `n` and ending at 1.
+fn collatz(n: f64) -> InterpreterResult<Data> {
+    if n < 1.0 || n.fract() != 0.0 {
+        return Err(InterpreterError::InvalidArguments("collatz".to_string()));
+    }
+
+    let mut n = n;
+    let mut sequence = vec![Data::Float(n)];
+    while n != 1.0 {
+        if sequence.len() >= ITERATION_LIMIT {
+            return Err(InterpreterError::IterationLimit("collatz".to_string()));
+        }
+        n = if n % 2.0 == 0.0 {
+            n / 2.0
+        } else {
+            3.0 * n + 1.0
+        };
+        sequence.push(Data::Float(n));
+    }
+
+    Ok(Data::List(sequence))
+}
+
+/// Converts `index` into a list index, truncating it. Fails unless it's within `0..=len`.
+fn list_index(index: f64, len: usize) -> InterpreterResult<usize> {
+    let truncated = index.trunc();
+    if truncated >= 0.0 && truncated <= len as f64 {
+        Ok(truncated as usize)
+    } else {
+        Err(InterpreterError::IndexOutOfRange(index, len))
+    }
+}
+
+/// Computes `n!` for `n` truncated to an integer. Results too large for a float saturate to
+/// infinity, and the factorial of `NaN` is `NaN`.
+fn factorial(n: f64) -> f64 {
+    if n.is_nan() {
+        return f64::NAN;
+    }
+    // 171! is the first factorial too large for a float.
+    if n >= 171.0 {
+        return f64::INFINITY;
+    }
+    (2..=n.trunc() as u64).fold(1.0, |product, i| product * i as f64)
+}
+
+/// Computes the greatest common divisor of `a` and `b`, truncated to integers, with Euclid's
+/// algorithm.
+fn gcd(a: f64, b: f64) -> f64 {
+    let (mut a, mut b) = (a.trunc().abs() as u64, b.trunc().abs() as u64);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a as f64
+}
+
+/// Computes the least common multiple of `a` and `b`, truncated to integers.
+fn lcm(a: f64, b: f64) -> f64 {
+    let (a, b) = (a.trunc().abs(), b.trunc().abs());
+    a / gcd(a, b) * b
+}
+
+/// Appends the elements of `data` and all lists nested in it to `out`, in order.
+fn flatten(data: Data, out: &mut Vec<Data>) {
+    match data {
+        Data::List(values) => values.into_iter().for_each(|value| flatten(value, out)),
+        data => out.push(data),
+    }
+}
+
+/// Converts a flat list of floats into a [`Vec<f64>`], failing for anything else.
+fn float_list(data: Data, name: &str) -> InterpreterResult<Vec<f64>> {
+    let Data::List(values) = data else {
+        return Err(InterpreterError::InvalidArguments(name.to_string()));
+    };
+
+    values
+        .into_iter()
+        .map(|value| match value {
+            Data::Float(value) => Ok(value),
+            _ => Err(InterpreterError::InvalidArguments(name.to_string())),
+        })
+        .collect()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Returns the population standard deviation of `values`.
+fn stddev(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64;
+    variance.sqrt()
+}
+
+/// Solves `a * x = b` using Gaussian elimination with partial pivoting.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> InterpreterResult<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        if a[pivot][col].abs() < 1e-12 {
+            return Err(InterpreterError::SingularMatrix);
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (top, bottom) = a.split_at_mut(col + 1);
+        let pivot_row = &top[col];
+        for (offset, row) in bottom.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (value, pivot_value) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot_value;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Ok(x)
+}
+
+/// Applies `func` to a number or to every number in a (nested) list, stopping at the first
+/// error.
+fn apply_func(
+    data: Data,
+    func: impl Fn(f64) -> InterpreterResult<Data> + Copy,
+) -> InterpreterResult<Data> {
     match data {
         Data::Float(value) => func(value),
-        Data::List(values) => Data::List(
+        Data::List(values) => Ok(Data::List(
             values
                 .into_iter()
                 .map(|data| apply_func(data, func))
-                .collect(),
-        ),
+                .collect::<InterpreterResult<Vec<Data>>>()?,
+        )),
+        Data::Str(value) => Err(InterpreterError::ExpectedNumber(value)),
+    }
+}
+
+/// Whether `value` is a valid argument of the built-in `name`, for the built-ins that are only
+/// defined for some numbers.
+fn in_domain(name: &str, value: f64) -> bool {
+    match name {
+        "sqrt" => value >= 0.0,
+        "ln" => value > 0.0,
+        "asin" | "acos" => (-1.0..=1.0).contains(&value),
+        _ => true,
+    }
+}
+
+/// Returns the length of every level of `data`, outermost first, failing for ragged lists.
+/// Numbers and strings have no levels.
+fn shape(data: &Data) -> InterpreterResult<Vec<usize>> {
+    let Data::List(values) = data else {
+        return Ok(Vec::new());
+    };
+    let mut rows: Option<Vec<usize>> = None;
+    for value in values {
+        let row = shape(value)?;
+        match &rows {
+            Some(rows) if *rows != row => return Err(InterpreterError::RaggedList),
+            Some(_) => {}
+            None => rows = Some(row),
+        }
+    }
+
+    let mut out = vec![values.len()];
+    out.extend(rows.unwrap_or_default());
+    Ok(out)
+}
+
+/// Applies `func` to the arguments of a multi-argument built-in, broadcasting over lists.
+///
+/// Scalars are repeated for every element, lists of length 1 are stretched to the length of the
+/// other lists, and all remaining lists must have the same length.
+fn broadcast(args: &[Data], func: fn(&[f64]) -> f64) -> InterpreterResult<Data> {
+    // A short row of a ragged list would otherwise be stretched like a list of length 1.
+    if args
+        .iter()
+        .filter(|arg| matches!(arg, Data::List(_)))
+        .count()
+        > 1
+    {
+        for arg in args {
+            shape(arg)?;
+        }
+    }
+
+    let mut len: Option<usize> = None;
+    for arg in args {
+        if let Data::List(values) = arg {
+            match len {
+                Some(1) | None => len = Some(values.len()),
+                Some(len) if values.len() != len && values.len() != 1 => {
+                    return Err(InterpreterError::InvalidListLength)
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    let Some(len) = len else {
+        let values = args
+            .iter()
+            .map(|arg| match arg {
+                Data::Float(value) => Ok(*value),
+                _ => Err(InterpreterError::ExpectedNumber(arg.to_string())),
+            })
+            .collect::<InterpreterResult<Vec<f64>>>()?;
+        return Ok(Data::Float(func(&values)));
+    };
+
+    let mut out: Vec<Data> = Vec::new();
+    for i in 0..len {
+        let args: Vec<Data> = args
+            .iter()
+            .map(|arg| match arg {
+                Data::List(values) if values.len() == 1 => values[0].clone(),
+                Data::List(values) => values[i].clone(),
+                _ => arg.clone(),
+            })
+            .collect();
+        out.push(broadcast(&args, func)?);
     }
+
+    Ok(Data::List(out))
 }
 
 type Scope = Vec<String>;
 
+/// A parameter of a user-defined function.
+struct FunctionParameter {
+    name: String,
+    default: Option<Expr>,
+    annotation: Option<ParamType>,
+}
+
+/// A user-defined function.
+struct Function {
+    parameters: Vec<FunctionParameter>,
+    /// The trailing parameter collecting the remaining arguments, if any.
+    rest: Option<FunctionParameter>,
+    body: Expr,
+}
+
+/// Fails if `arg` doesn't match the `annotation` of the parameter `parameter` of `function`.
+fn check_argument(
+    function: &str,
+    parameter: &str,
+    annotation: Option<ParamType>,
+    arg: &Data,
+) -> InterpreterResult<()> {
+    match (annotation, arg) {
+        (None, _)
+        | (Some(ParamType::Float), Data::Float(_))
+        | (Some(ParamType::List), Data::List(_)) => Ok(()),
+        (Some(annotation), _) => Err(InterpreterError::ArgumentType(
+            function.to_string(),
+            parameter.to_string(),
+            annotation,
+            arg.clone(),
+        )),
+    }
+}
+
 pub struct Interpreter {
     parsed: Vec<Parsed>,
     variables: HashMap<String, Data>,
-    functions: HashMap<String, (Vec<String>, Expr)>,
+    functions: HashMap<String, Function>,
+    output_format: OutputFormat,
+    precision: Option<usize>,
+    writer: Box<dyn Write>,
+    depth: Cell<usize>,
+    recursion_limit: usize,
+    /// The number of statements, loop iterations and function calls run so far.
+    steps: Cell<usize>,
+    /// The maximum number of steps, unlimited if [`None`].
+    step_limit: Option<usize>,
+    /// Whether operations producing `inf` or `NaN` are errors.
+    strict_finite: bool,
+    /// Whether built-ins like `sqrt` fail for arguments they aren't defined for.
+    strict_domain: bool,
+    /// The state of the xorshift generator used by `random`.
+    rng: Cell<u64>,
+    /// The files currently being imported, used to detect import cycles.
+    imports: Vec<PathBuf>,
 }
 
+/// The default maximum depth of nested user-defined function calls. Evaluation recurses on the
+/// caller's stack, and this many calls fit in the 2 MiB stack of a spawned thread even in debug
+/// builds. Callers running on a larger stack can raise it with
+/// [`Interpreter::set_recursion_limit`].
+const RECURSION_LIMIT: usize = 200;
+
 type InterpreterResult<T> = std::result::Result<T, InterpreterError>;
 
 impl Interpreter {
@@ -157,21 +799,109 @@ impl Interpreter {
             parsed,
             variables: HashMap::new(),
             functions: HashMap::new(),
+            output_format: OutputFormat::Plain,
+            precision: None,
+            writer: Box::new(stdout()),
+            depth: Cell::new(0),
+            recursion_limit: RECURSION_LIMIT,
+            steps: Cell::new(0),
+            step_limit: None,
+            strict_finite: false,
+            strict_domain: false,
+            rng: Cell::new(rng_state(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64),
+            )),
+            imports: Vec::new(),
+        }
+    }
+
+    /// Sets where printed values are written to. Defaults to stdout.
+    pub fn set_writer(&mut self, writer: Box<dyn Write>) {
+        self.writer = writer;
+    }
+
+    /// Sets the path of the file being run, so that a file importing it back is reported as an
+    /// import cycle.
+    pub fn set_file_path(&mut self, file_path: &str) -> std::io::Result<()> {
+        self.imports = vec![canonicalize(file_path)?];
+        Ok(())
+    }
+
+    /// Sets the maximum depth of nested user-defined function calls.
+    pub fn set_recursion_limit(&mut self, recursion_limit: usize) {
+        self.recursion_limit = recursion_limit;
+    }
+
+    /// Sets the maximum number of statements, loop iterations and function calls run before
+    /// stopping with an error. [`None`] is unlimited, which is the default.
+    pub fn set_step_limit(&mut self, step_limit: Option<usize>) {
+        self.step_limit = step_limit;
+    }
+
+    /// Counts a step, failing if the step limit is exceeded.
+    fn step(&self) -> InterpreterResult<()> {
+        self.charge(1)
+    }
+
+    /// Counts `steps` steps at once, for built-ins producing that many elements.
+    fn charge(&self, steps: usize) -> InterpreterResult<()> {
+        self.steps.set(self.steps.get().saturating_add(steps));
+        match self.step_limit {
+            Some(limit) if self.steps.get() > limit => {
+                Err(InterpreterError::StepLimitExceeded(limit))
+            }
+            _ => Ok(()),
         }
     }
 
+    /// Converts `count`, the number of elements the built-in `name` is asked to produce, to a
+    /// [`usize`], counting a step for each. Fails unless it's a whole number, and if it's larger
+    /// than [`ITERATION_LIMIT`].
+    fn element_count(&self, name: &str, count: f64) -> InterpreterResult<usize> {
+        if !(count >= 0.0 && count.fract() == 0.0) {
+            return Err(InterpreterError::InvalidArguments(name.to_string()));
+        }
+        // Casting saturates, so huge counts use up any step limit.
+        self.charge(count as usize)?;
+        if count > ITERATION_LIMIT as f64 {
+            return Err(InterpreterError::IterationLimit(name.to_string()));
+        }
+        Ok(count as usize)
+    }
+
+    /// Makes operations and built-in functions that produce `inf` or `NaN` fail instead of
+    /// returning the value. Disabled by default.
+    pub fn set_strict_finite(&mut self, strict_finite: bool) {
+        self.strict_finite = strict_finite;
+    }
+
+    /// Makes `sqrt`, `ln`, `asin` and `acos` fail for arguments they aren't defined for instead
+    /// of returning `NaN` or `-inf`. Disabled by default.
+    pub fn set_strict_domain(&mut self, strict_domain: bool) {
+        self.strict_domain = strict_domain;
+    }
+
+    /// Sets how printed values are formatted.
+    pub fn set_output_format(&mut self, output_format: OutputFormat) {
+        self.output_format = output_format;
+    }
+
+    /// Sets the number of decimal places printed numbers are rounded to. [`None`] prints them
+    /// exactly, which is the default.
+    pub fn set_precision(&mut self, precision: Option<usize>) {
+        self.precision = precision;
+    }
+
     fn get_variable(&self, name: &str) -> Option<Data> {
         match name {
             "PI" => Some(Data::Float(PI)),
             "TAU" => Some(Data::Float(PI * 2.0)),
             "GLR" => Some(Data::Float(1.618_033_988_749_894f64)), // Golden ratio
-            _ => {
-                let Some(data) = self.variables.get(name) else {
-                    return None;
-                };
-
-                Some(data.clone())
-            }
+            "inf" => Some(Data::Float(f64::INFINITY)),
+            "nan" => Some(Data::Float(f64::NAN)),
+            _ => self.variables.get(name).cloned(),
         }
     }
 
@@ -193,18 +923,21 @@ impl Interpreter {
                 if let Some(data) = self.get_variable(name) {
                     return Ok(data.into());
                 }
+                // A function name passed to a function like `map`.
+                if self.function_exits(name) {
+                    return Ok(expr.clone());
+                }
                 return Err(InterpreterError::UndefinedVariable(name.to_string()));
             }
-            Expr::FunctionCall(name, args) => match name {
-                _ => {
-                    if let Some((parameters, expr2)) = self.functions.get(name) {
-                        out =
-                            self.transform_fn_expr((parameters.to_vec(), args.to_vec()), expr2)?;
-                    } else {
-                        return Err(InterpreterError::UndefinedFunction(name.to_string()));
-                    }
-                }
-            },
+            Expr::FunctionCall(name, call_args) => {
+                // Only the arguments are substituted, the call itself is resolved once the
+                // expression is evaluated.
+                let call_args = call_args
+                    .iter()
+                    .map(|arg| self.transform_fn_expr((parameters.to_vec(), args.to_vec()), arg))
+                    .collect::<InterpreterResult<Vec<Expr>>>()?;
+                out = Expr::FunctionCall(name.to_string(), call_args);
+            }
             Expr::Expr(left, op, right) => {
                 let left_ =
                     self.transform_fn_expr((parameters.to_vec(), args.to_vec()), left.as_ref())?;
@@ -212,246 +945,2266 @@ impl Interpreter {
                     self.transform_fn_expr((parameters.to_vec(), args.to_vec()), right.as_ref())?;
                 out = Expr::Expr(Box::new(left_), op.clone(), Box::new(right_));
             }
+            Expr::Comparison(first, chain) => {
+                let first =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), first.as_ref())?;
+                let chain = chain
+                    .iter()
+                    .map(|(op, expr)| {
+                        Ok((
+                            op.clone(),
+                            self.transform_fn_expr((parameters.to_vec(), args.to_vec()), expr)?,
+                        ))
+                    })
+                    .collect::<InterpreterResult<Vec<(Operator, Expr)>>>()?;
+                out = Expr::Comparison(Box::new(first), chain);
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                let condition = self
+                    .transform_fn_expr((parameters.to_vec(), args.to_vec()), condition.as_ref())?;
+                let then =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), then.as_ref())?;
+                let otherwise = self
+                    .transform_fn_expr((parameters.to_vec(), args.to_vec()), otherwise.as_ref())?;
+                out = Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise));
+            }
             Expr::List(exprs) => {
                 return Ok(Expr::List(
                     exprs
                         .iter()
                         .map(|expr| {
                             self.transform_fn_expr((parameters.clone(), args.clone()), expr)
-                                .unwrap_or_else(|err| panic!("{:?}", err))
                         })
-                        .collect(),
+                        .collect::<InterpreterResult<Vec<Expr>>>()?,
                 ))
             }
-            Expr::FloatLiteral(_) | Expr::NegFloatLiteral(_) => out = expr.clone(),
+            Expr::Comprehension(body, name, list, filter) => {
+                let list =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), list.as_ref())?;
+                // The bound variable shadows parameters of the same name and is left in place.
+                let mut parameters = parameters.to_vec();
+                let mut args = args.to_vec();
+                parameters.insert(0, name.to_string());
+                args.insert(0, Expr::Ident(name.to_string()));
+                let body =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), body.as_ref())?;
+                let filter = filter
+                    .as_ref()
+                    .map(|filter| self.transform_fn_expr((parameters, args), filter.as_ref()))
+                    .transpose()?
+                    .map(Box::new);
+                out = Expr::Comprehension(Box::new(body), name.to_string(), Box::new(list), filter);
+            }
+            Expr::Index(list, index) => {
+                let list =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), list.as_ref())?;
+                let index =
+                    self.transform_fn_expr((parameters.to_vec(), args.to_vec()), index.as_ref())?;
+                out = Expr::Index(Box::new(list), Box::new(index));
+            }
+            Expr::Lambda(lambda_parameters, body) => {
+                // The lambda's parameters shadow the outer ones and are left in place.
+                let mut parameters = parameters.to_vec();
+                let mut args = args.to_vec();
+                for name in lambda_parameters.iter().rev() {
+                    parameters.insert(0, name.to_string());
+                    args.insert(0, Expr::Ident(name.to_string()));
+                }
+                let body = self.transform_fn_expr((parameters, args), body.as_ref())?;
+                out = Expr::Lambda(lambda_parameters.to_vec(), Box::new(body));
+            }
+            Expr::FloatLiteral(_) | Expr::NegFloatLiteral(_) | Expr::StringLiteral(_) => {
+                out = expr.clone()
+            }
         };
 
         Ok(out)
     }
 
-    fn evaluate_expr(&self, expr: &Expr) -> InterpreterResult<Data> {
-        match expr {
-            Expr::Ident(name) => {
-                if let Some(data) = self.get_variable(name) {
-                    return Ok(data.clone());
-                } else {
-                    return Err(InterpreterError::UndefinedVariable(name.to_string()));
-                }
-            }
-            Expr::FloatLiteral(value) => Ok(Data::Float(
-                value
-                    .parse()
-                    .map_err(|_| unreachable!("Invalid value: {:?}", value))?,
-            )),
-            Expr::Expr(left, op, right) => {
-                let left = self.evaluate_expr(&left)?;
-                let right = self.evaluate_expr(&right)?;
-                apply_op(left, right, op.clone())
-            }
-            Expr::NegFloatLiteral(value) => {
-                let value_f64: f64 = value
-                    .parse()
-                    .map_err(|_| unreachable!("Invalid value: {:?}", value))?;
-                Ok(Data::Float(-1.0 * value_f64))
-            }
-            Expr::FunctionCall(name, args) => match name.as_str() {
-                "sin" => {
-                    if args.len() > 1 {
-                        return Err(InterpreterError::InvalidArguments("sin".to_string()));
-                    }
-                    let arg = self.evaluate_expr(&args[0])?;
-                    Ok(apply_func(arg, |arg| Data::Float(arg.sin())))
-                }
-                "cos" => {
-                    if args.len() > 1 {
-                        return Err(InterpreterError::InvalidArguments("cos".to_string()));
-                    }
-                    let arg = self.evaluate_expr(&args[0])?;
-                    Ok(apply_func(arg, |arg| Data::Float(arg.cos())))
-                }
-                "tan" => {
-                    if args.len() > 1 {
-                        return Err(InterpreterError::InvalidArguments("tan".to_string()));
-                    }
-                    let arg = self.evaluate_expr(&args[0])?;
-                    Ok(apply_func(arg, |arg| Data::Float(arg.tan())))
-                }
-                _ => {
-                    let Some((parameters, expr)) = self.functions.get(name) else {
-                        return Err(InterpreterError::UndefinedFunction(name.to_string()));
-                    };
+    /// Evaluates the arguments of the built-in `name`, which takes exactly `count` arguments.
+    fn evaluate_args(
+        &self,
+        name: &str,
+        args: &[Expr],
+        count: usize,
+    ) -> InterpreterResult<Vec<Data>> {
+        if args.len() != count {
+            return Err(InterpreterError::InvalidArguments(name.to_string()));
+        }
 
-                    if args.len() != parameters.len() {
-                        return Err(InterpreterError::InvalidArguments(name.to_string()));
-                    }
+        args.iter().map(|arg| self.evaluate_expr(arg)).collect()
+    }
 
-                    let parsable =
-                        self.transform_fn_expr((parameters.to_vec(), args.to_vec()), expr)?;
-                    return Ok(self.evaluate_expr(&parsable)?);
-                }
-            },
-            Expr::List(exprs) => {
-                let vals = exprs
-                    .iter()
-                    .map(|expr| {
-                        self.evaluate_expr(expr)
-                            .unwrap_or_else(|err| panic!("{:?}", err))
-                    })
-                    .collect();
+    /// Returns the next random number in `[0, 1)`, advancing the xorshift generator.
+    fn next_random(&self) -> f64 {
+        let mut x = self.rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng.set(x);
+        // The top 53 bits fill the mantissa exactly.
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
 
-                Ok(Data::List(vals))
+    /// Calls `callable`, a lambda or the name of a function, with `args` on behalf of the
+    /// built-in `name`.
+    fn call_callable(
+        &self,
+        name: &str,
+        callable: &Expr,
+        args: Vec<Data>,
+    ) -> InterpreterResult<Data> {
+        self.step()?;
+        let args: Vec<Expr> = args.into_iter().map(Expr::from).collect();
+        match callable {
+            Expr::Lambda(parameters, body) => {
+                if parameters.len() != args.len() {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                }
+                let body = self.transform_fn_expr((parameters.to_vec(), args), body)?;
+                self.evaluate_expr(&body)
             }
+            Expr::Ident(function) if self.function_exits(function) => {
+                self.evaluate_expr(&Expr::FunctionCall(function.to_string(), args))
+            }
+            _ => Err(InterpreterError::InvalidArguments(name.to_string())),
         }
     }
 
-    fn clean_scope(&mut self, scope: Scope) {
-        for name in &scope {
-            self.variables.remove(name);
-            self.functions.remove(name);
+    /// Calls the user-defined function `name`.
+    fn call_function(&self, name: &str, args: &[Expr]) -> InterpreterResult<Data> {
+        let parsable = self.bind_arguments(name, args)?;
+
+        if self.depth.get() >= self.recursion_limit {
+            return Err(InterpreterError::RecursionLimit(name.to_string()));
         }
+        self.step()?;
+        self.depth.set(self.depth.get() + 1);
+        let out = self.evaluate_expr(&parsable);
+        self.depth.set(self.depth.get() - 1);
+        out
     }
 
-    fn function_exits(&self, name: &str) -> bool {
-        match name {
-            "sin" | "cos" | "tan" => true,
-            _ => self.functions.get(name).is_some(),
+    /// Evaluates the arguments of the user-defined function `name` and substitutes them into its
+    /// body. Kept out of [`Interpreter::call_function`] so that recursive calls don't pay for its
+    /// stack frame.
+    #[inline(never)]
+    fn bind_arguments(&self, name: &str, args: &[Expr]) -> InterpreterResult<Expr> {
+        let Some(Function {
+            parameters,
+            rest,
+            body,
+        }) = self.functions.get(name)
+        else {
+            return Err(InterpreterError::UndefinedFunction(name.to_string()));
+        };
+
+        if args.len() > parameters.len() && rest.is_none() {
+            return Err(InterpreterError::InvalidArguments(name.to_string()));
+        }
+
+        let mut args = args
+            .iter()
+            .map(|arg| self.evaluate_expr(arg))
+            .collect::<InterpreterResult<Vec<Data>>>()?;
+        // Arguments after the ones for the other parameters are collected by the rest parameter.
+        let rest_args = args.split_off(args.len().min(parameters.len()));
+        for (parameter, arg) in parameters.iter().zip(&args) {
+            check_argument(name, &parameter.name, parameter.annotation, arg)?;
         }
+        let mut args: Vec<Expr> = args.into_iter().map(Expr::from).collect();
+        let mut names: Vec<String> = parameters
+            .iter()
+            .map(|parameter| parameter.name.clone())
+            .collect();
+        // Missing arguments take their default values, which can refer to the parameters
+        // before them.
+        for parameter in &parameters[args.len()..] {
+            let Some(default) = &parameter.default else {
+                return Err(InterpreterError::InvalidArguments(name.to_string()));
+            };
+            let default =
+                self.transform_fn_expr((names[..args.len()].to_vec(), args.to_vec()), default)?;
+            let default = self.evaluate_expr(&default)?;
+            check_argument(name, &parameter.name, parameter.annotation, &default)?;
+            args.push(Expr::from(default));
+        }
+        if let Some(rest) = rest {
+            for arg in &rest_args {
+                check_argument(name, &rest.name, rest.annotation, arg)?;
+            }
+            names.push(rest.name.clone());
+            args.push(Expr::from(Data::List(rest_args)));
+        }
+        self.transform_fn_expr((names, args), body)
     }
 
-    fn execute_block(&mut self, block: Vec<Parsed>) -> Result<Scope> {
-        let mut current = 0usize;
-        let mut scope: Scope = Vec::new();
-        while block.get(current).is_some() {
-            let parsed = block.get(current).unwrap().clone();
-            match parsed {
-                Parsed::Declaration(Token(TokenType::Ident(name), loc), expr) => {
-                    if let Some(_) = self.get_variable(&name) {
-                        return Err(error!(
-                            Other,
-                            "Re-decleration of variable {:?} at {}", name, loc
-                        ));
-                    }
-                    self.variables
-                        .insert(name.to_string(), self.evaluate_expr(&expr)?);
-                    scope.push(name.to_string());
-                }
-                Parsed::PrintExpr(expr) => {
-                    let value = self.evaluate_expr(&expr)?;
-                    println!("{}", value);
-                }
-                Parsed::FunctionDecleration(Token(TokenType::Ident(f), loc), parameters, expr) => {
-                    if self.function_exits(&f) {
-                        return Err(error!(
-                            Other,
-                            "Re-decleration of function {:?} at {}", f, loc
-                        ));
+    /// Calls the built-in function `name`. Kept out of [`Interpreter::evaluate_expr`] so that
+    /// nested user-defined function calls don't pay for its stack frame.
+    #[inline(never)]
+    fn call_builtin(&self, name: &str, args: &[Expr]) -> InterpreterResult<Data> {
+        match name {
+            "sin" => {
+                let arg = self.evaluate_args("sin", args, 1)?.remove(0);
+                apply_func(arg, |arg| Ok(Data::Float(arg.sin())))
+            }
+            "cos" => {
+                let arg = self.evaluate_args("cos", args, 1)?.remove(0);
+                apply_func(arg, |arg| Ok(Data::Float(arg.cos())))
+            }
+            "tan" => {
+                let arg = self.evaluate_args("tan", args, 1)?.remove(0);
+                apply_func(arg, |arg| Ok(Data::Float(arg.tan())))
+            }
+            "collatz" => {
+                if args.len() != 1 {
+                    return Err(InterpreterError::InvalidArguments("collatz".to_string()));
+                }
+                let Data::Float(n) = self.evaluate_expr(&args[0])? else {
+                    return Err(InterpreterError::InvalidArguments("collatz".to_string()));
+                };
+                collatz(n)
+            }
+            "zip" => {
+                if args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments("zip".to_string()));
+                }
+                let (Data::List(left), Data::List(right)) =
+                    (self.evaluate_expr(&args[0])?, self.evaluate_expr(&args[1])?)
+                else {
+                    return Err(InterpreterError::InvalidArguments("zip".to_string()));
+                };
+                // Like `apply_op`, lists of different lengths are rejected instead of truncated.
+                if left.len() != right.len() {
+                    return Err(InterpreterError::InvalidListLength);
+                }
+                Ok(Data::List(
+                    left.into_iter()
+                        .zip(right)
+                        .map(|(left, right)| Data::List(vec![left, right]))
+                        .collect(),
+                ))
+            }
+            "transpose" => {
+                let Data::List(rows) = self.evaluate_args("transpose", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("transpose".to_string()));
+                };
+                let rows = rows
+                    .into_iter()
+                    .map(|row| match row {
+                        Data::List(values) => Ok(values),
+                        _ => Err(InterpreterError::InvalidArguments("transpose".to_string())),
+                    })
+                    .collect::<InterpreterResult<Vec<Vec<Data>>>>()?;
+                let width = rows.first().map_or(0, Vec::len);
+                if rows.iter().any(|row| row.len() != width) {
+                    return Err(InterpreterError::RaggedList);
+                }
+                Ok(Data::List(
+                    (0..width)
+                        .map(|i| Data::List(rows.iter().map(|row| row[i].clone()).collect()))
+                        .collect(),
+                ))
+            }
+            "dot" => {
+                if args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments("dot".to_string()));
+                }
+                let (Data::List(left), Data::List(right)) =
+                    (self.evaluate_expr(&args[0])?, self.evaluate_expr(&args[1])?)
+                else {
+                    return Err(InterpreterError::InvalidArguments("dot".to_string()));
+                };
+                if left.len() != right.len() {
+                    return Err(InterpreterError::InvalidListLength);
+                }
+                let mut sum = 0.0;
+                for (left, right) in left.iter().zip(&right) {
+                    let (Data::Float(left), Data::Float(right)) = (left, right) else {
+                        return Err(InterpreterError::InvalidArguments("dot".to_string()));
+                    };
+                    sum += left * right;
+                }
+                Ok(Data::Float(sum))
+            }
+            "matrix_solve" => {
+                if args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments(
+                        "matrix_solve".to_string(),
+                    ));
+                }
+                let Data::List(rows) = self.evaluate_expr(&args[0])? else {
+                    return Err(InterpreterError::InvalidArguments(
+                        "matrix_solve".to_string(),
+                    ));
+                };
+                let a = rows
+                    .into_iter()
+                    .map(|row| float_list(row, "matrix_solve"))
+                    .collect::<InterpreterResult<Vec<Vec<f64>>>>()?;
+                let b = float_list(self.evaluate_expr(&args[1])?, "matrix_solve")?;
+                if a.len() != b.len() || a.iter().any(|row| row.len() != b.len()) {
+                    return Err(InterpreterError::InvalidListLength);
+                }
+                let x = solve_linear(a, b)?;
+                Ok(Data::List(x.into_iter().map(Data::Float).collect()))
+            }
+            "sqrt" | "ln" | "asin" | "acos" => {
+                let arg = self.evaluate_args(name, args, 1)?.remove(0);
+                let func: fn(f64) -> f64 = match name {
+                    "sqrt" => f64::sqrt,
+                    "ln" => f64::ln,
+                    "asin" => f64::asin,
+                    _ => f64::acos,
+                };
+                let strict_domain = self.strict_domain;
+                apply_func(arg, move |arg| {
+                    if strict_domain && !in_domain(name, arg) {
+                        return Err(InterpreterError::DomainError(name.to_string(), arg));
                     }
-                    let parameters = parameters
+                    Ok(Data::Float(func(arg)))
+                })
+            }
+            "atan" | "sinh" | "cosh" | "tanh" | "sign" | "trunc" => {
+                let arg = self.evaluate_args(name, args, 1)?.remove(0);
+                let func: fn(f64) -> InterpreterResult<Data> = match name {
+                    "atan" => |arg| Ok(Data::Float(arg.atan())),
+                    "sinh" => |arg| Ok(Data::Float(arg.sinh())),
+                    "cosh" => |arg| Ok(Data::Float(arg.cosh())),
+                    "sign" => |arg| Ok(Data::Float(if arg == 0.0 { 0.0 } else { arg.signum() })),
+                    "trunc" => |arg| Ok(Data::Float(arg.trunc())),
+                    _ => |arg| Ok(Data::Float(arg.tanh())),
+                };
+                apply_func(arg, func)
+            }
+            "atan2" => {
+                let args = self.evaluate_args("atan2", args, 2)?;
+                broadcast(&args, |args| args[0].atan2(args[1]))
+            }
+            "factorial" => {
+                let arg = self.evaluate_args("factorial", args, 1)?.remove(0);
+                apply_func(arg, |arg| {
+                    if arg < 0.0 {
+                        return Err(InterpreterError::InvalidArguments("factorial".to_string()));
+                    }
+                    Ok(Data::Float(factorial(arg)))
+                })
+            }
+            "exp" => {
+                let arg = self.evaluate_args("exp", args, 1)?.remove(0);
+                apply_func(arg, |arg| Ok(Data::Float(arg.exp())))
+            }
+            "pow" => {
+                let args = self.evaluate_args("pow", args, 2)?;
+                broadcast(&args, |args| args[0].powf(args[1]))
+            }
+            "mod" => {
+                let args = self.evaluate_args("mod", args, 2)?;
+                let mut divisors = Vec::new();
+                flatten(args[1].clone(), &mut divisors);
+                if divisors.contains(&Data::Float(0.0)) {
+                    return Err(InterpreterError::InvalidArguments("mod".to_string()));
+                }
+                broadcast(&args, |args| args[0].rem_euclid(args[1]))
+            }
+            "clamp" => {
+                let args = self.evaluate_args("clamp", args, 3)?;
+                broadcast(&args, |args| args[0].max(args[1]).min(args[2]))
+            }
+            "gcd" | "lcm" => {
+                let args = self.evaluate_args(name, args, 2)?;
+                if name == "lcm" {
+                    let mut values = Vec::new();
+                    args.iter()
+                        .for_each(|arg| flatten(arg.clone(), &mut values));
+                    if values
                         .iter()
-                        .map(|Token(t, _)| {
-                            if let TokenType::Ident(name) = t {
-                                name.to_string()
-                            } else {
-                                unreachable!("Internal error!");
-                            }
-                        })
-                        .collect();
-                    self.functions
-                        .insert(f.to_string(), (parameters, expr.clone()));
-                    scope.push(f.to_string());
-                }
-                Parsed::FromLoop(min_expr, max_expr, ident_expr, step_expr, block) => {
-                    let min = match self.evaluate_expr(&min_expr)? {
-                        Data::Float(value) => value,
-                        Data::List(_) => {
-                            return Err(error!(Other, "From-to-as-loop cannot contain list"))
-                        }
-                    };
-                    let max = match self.evaluate_expr(&max_expr)? {
-                        Data::Float(value) => value,
-                        Data::List(_) => {
-                            return Err(error!(Other, "From-to-as-loop cannot contain list"))
-                        }
-                    };
-                    let step = match self.evaluate_expr(&step_expr)? {
-                        Data::Float(value) => value,
-                        Data::List(_) => {
-                            return Err(error!(Other, "From-to-as-loop cannot contain list"))
-                        }
-                    };
-                    let Expr::Ident(name) = ident_expr else {
-                        unreachable!("Internal error!");
-                    };
-                    let mut i = min;
-                    self.variables.insert(name.to_string(), Data::Float(i));
-                    while i <= max {
-                        let scope = self.execute_block(block.to_vec())?;
-                        self.clean_scope(scope);
-                        i += step;
-                        if let Some(value) = self.variables.get_mut(&name) {
-                            *value = Data::Float(i);
-                        }
+                        .any(|value| matches!(value, Data::Float(value) if value.trunc() == 0.0))
+                    {
+                        return Err(InterpreterError::InvalidArguments("lcm".to_string()));
                     }
-                    self.variables.remove(&name);
                 }
-                Parsed::Block(block) => {
-                    let scope = self.execute_block(block)?;
-                    self.clean_scope(scope);
+                broadcast(
+                    &args,
+                    match name {
+                        "gcd" => |args: &[f64]| gcd(args[0], args[1]),
+                        _ => |args: &[f64]| lcm(args[0], args[1]),
+                    },
+                )
+            }
+            "round" => {
+                // `round(x, n)` rounds to `n` decimal places, `round(x)` to a whole number.
+                if args.len() != 1 && args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments("round".to_string()));
                 }
-                Parsed::ForLoop(ident_expr, list_expr, block) => {
-                    let list = match self.evaluate_expr(&list_expr)? {
-                        Data::List(datas) => datas,
-                        Data::Float(_) => return Err(error!(Other, "Expected list!")),
-                    };
-                    let Expr::Ident(name) = ident_expr else {
-                        return Err(error!(Other, "Expected identefier!"));
-                    };
-                    self.variables
-                        .insert(name.clone(), list.get(0).unwrap().clone());
-                    for data in &list[1..] {
-                        let scope = self.execute_block(block.clone())?;
-                        self.clean_scope(scope);
-                        if let Some(value) = self.variables.get_mut(&name) {
-                            *value = data.clone();
-                        }
+                let args = self.evaluate_args("round", args, args.len())?;
+                broadcast(&args, |args| match args {
+                    [x, n] => {
+                        let scale = 10f64.powf(n.trunc());
+                        (x * scale).round() / scale
                     }
-                    self.variables.remove(&name);
+                    _ => args[0].round(),
+                })
+            }
+            "defined" => {
+                let Data::Str(name) = self.evaluate_args("defined", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("defined".to_string()));
+                };
+                Ok(Data::from(
+                    self.get_variable(&name).is_some() || self.function_exits(&name),
+                ))
+            }
+            "random" => {
+                // `random()` is in `[0, 1)`, `random(a, b)` in `[a, b)`.
+                if !args.is_empty() && args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments("random".to_string()));
                 }
-                Parsed::Destructuring(left, right) => {
-                    let Expr::List(left_exprs) = left else {
-                        return Err(error!(Other, "Some error!"));
-                    };
-                    let Expr::List(right_exprs) = right else {
-                        return Err(error!(Other, "Some error!"));
+                let args = self.evaluate_args("random", args, args.len())?;
+                let value = self.next_random();
+                match args[..] {
+                    [] => Ok(Data::Float(value)),
+                    [Data::Float(a), Data::Float(b)] => Ok(Data::Float(a + (b - a) * value)),
+                    _ => Err(InterpreterError::InvalidArguments("random".to_string())),
+                }
+            }
+            "randint" => {
+                let args = self.evaluate_args("randint", args, 2)?;
+                let [Data::Float(lo), Data::Float(hi)] = args[..] else {
+                    return Err(InterpreterError::InvalidArguments("randint".to_string()));
+                };
+                let (lo, hi) = (lo.ceil(), hi.floor());
+                if !lo.is_finite() || !hi.is_finite() || lo > hi {
+                    return Err(InterpreterError::InvalidArguments("randint".to_string()));
+                }
+                let value = (self.next_random() * (hi - lo + 1.0)).floor();
+                Ok(Data::Float(lo + value))
+            }
+            "shuffle" => {
+                let Data::List(mut values) = self.evaluate_args("shuffle", args, 1)?.remove(0)
+                else {
+                    return Err(InterpreterError::InvalidArguments("shuffle".to_string()));
+                };
+                // Fisher-Yates shuffle.
+                for i in (1..values.len()).rev() {
+                    let j = (self.next_random() * (i + 1) as f64) as usize;
+                    values.swap(i, j);
+                }
+                Ok(Data::List(values))
+            }
+            "seed" => {
+                let Data::Float(seed) = self.evaluate_args("seed", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("seed".to_string()));
+                };
+                self.rng.set(rng_state(seed.to_bits()));
+                Ok(Data::Float(seed))
+            }
+            "equals" => {
+                let args = self.evaluate_args("equals", args, 2)?;
+                Ok(Data::from(args[0] == args[1]))
+            }
+            "fill" => {
+                let mut args = self.evaluate_args("fill", args, 2)?;
+                let Data::Float(count) = args.remove(1) else {
+                    return Err(InterpreterError::InvalidArguments("fill".to_string()));
+                };
+                let count = self.element_count("fill", count)?;
+                Ok(Data::List(vec![args.remove(0); count]))
+            }
+            "linspace" => {
+                let args = self.evaluate_args("linspace", args, 3)?;
+                let [Data::Float(start), Data::Float(stop), Data::Float(count)] = args[..] else {
+                    return Err(InterpreterError::InvalidArguments("linspace".to_string()));
+                };
+                let count = self.element_count("linspace", count)?;
+                if count < 2 {
+                    return Err(InterpreterError::InvalidArguments("linspace".to_string()));
+                }
+                let step = (stop - start) / (count - 1) as f64;
+                // The last value is set directly so that it's exactly `stop`.
+                let mut values: Vec<Data> = (0..count - 1)
+                    .map(|i| Data::Float(start + step * i as f64))
+                    .collect();
+                values.push(Data::Float(stop));
+                Ok(Data::List(values))
+            }
+            "len" => {
+                let Data::List(values) = self.evaluate_args("len", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("len".to_string()));
+                };
+                Ok(Data::Float(values.len() as f64))
+            }
+            "first" | "last" => {
+                let Data::List(values) = self.evaluate_args(name, args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                };
+                let value = if name == "first" {
+                    values.first()
+                } else {
+                    values.last()
+                };
+                value
+                    .cloned()
+                    .ok_or_else(|| InterpreterError::InvalidArguments(name.to_string()))
+            }
+            "tail" => {
+                let Data::List(values) = self.evaluate_args("tail", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("tail".to_string()));
+                };
+                Ok(Data::List(values.into_iter().skip(1).collect()))
+            }
+            "flatten" => {
+                let data = self.evaluate_args("flatten", args, 1)?.remove(0);
+                if !matches!(data, Data::List(_)) {
+                    return Err(InterpreterError::InvalidArguments("flatten".to_string()));
+                }
+                let mut values = Vec::new();
+                flatten(data, &mut values);
+                Ok(Data::List(values))
+            }
+            "slice" => {
+                // `slice(list, start, end)` returns the elements from `start` up to, but not
+                // including, `end`. Leaving out `end` slices to the end of the list.
+                if args.len() != 2 && args.len() != 3 {
+                    return Err(InterpreterError::InvalidArguments("slice".to_string()));
+                }
+                let args = self.evaluate_args("slice", args, args.len())?;
+                let Data::List(values) = &args[0] else {
+                    return Err(InterpreterError::InvalidArguments("slice".to_string()));
+                };
+                let mut bounds = Vec::new();
+                for arg in &args[1..] {
+                    let Data::Float(index) = arg else {
+                        return Err(InterpreterError::InvalidArguments("slice".to_string()));
                     };
-                    if left_exprs.len() != right_exprs.len() {
-                        return Err(error!(Other, "Too few idents in destructor!"));
+                    bounds.push(list_index(*index, values.len())?);
+                }
+                let start = bounds[0];
+                let end = bounds.get(1).copied().unwrap_or(values.len());
+                if start > end {
+                    return Err(InterpreterError::InvalidArguments("slice".to_string()));
+                }
+                Ok(Data::List(values[start..end].to_vec()))
+            }
+            "contains" => {
+                // Only the top level is searched, `contains([[1]], 1)` is 0.
+                let mut args = self.evaluate_args("contains", args, 2)?;
+                let value = args.remove(1);
+                let Data::List(values) = args.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("contains".to_string()));
+                };
+                Ok(Data::from(values.contains(&value)))
+            }
+            "map" | "filter" => {
+                let [list, callable] = args else {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                };
+                let Data::List(values) = self.evaluate_expr(list)? else {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                };
+                let mut out = Vec::new();
+                for value in values {
+                    let result = self.call_callable(name, callable, vec![value.clone()])?;
+                    if name == "map" {
+                        out.push(result);
+                    } else if is_truthy(result)? {
+                        out.push(value);
                     }
-                    for (left, right) in left_exprs.iter().zip(right_exprs) {
-                        let Expr::Ident(name) = left else {
-                            return Err(error!(Other, "Only idents allowed in destructor!"));
-                        };
-
-                        let None = self.variables.get(name) else {
-                            return Err(error!(Other, "Re-decleration of variable {:?}", name));
-                        };
+                }
+                Ok(Data::List(out))
+            }
+            "reduce" => {
+                // `reduce(list, f)` starts with the first element, `reduce(list, f, initial)`
+                // with `initial`.
+                let (list, callable, initial) = match args {
+                    [list, callable] => (list, callable, None),
+                    [list, callable, initial] => (list, callable, Some(initial)),
+                    _ => return Err(InterpreterError::InvalidArguments(name.to_string())),
+                };
+                let Data::List(values) = self.evaluate_expr(list)? else {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                };
+                let mut values = values.into_iter();
+                let initial = match initial {
+                    Some(initial) => self.evaluate_expr(initial)?,
+                    None => values
+                        .next()
+                        .ok_or_else(|| InterpreterError::InvalidArguments(name.to_string()))?,
+                };
+                values.try_fold(initial, |acc, value| {
+                    self.call_callable(name, callable, vec![acc, value])
+                })
+            }
+            "count" | "index_of" => {
+                let mut args = self.evaluate_args(name, args, 2)?;
+                let value = args.remove(1);
+                let Data::List(values) = args.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                };
+                if name == "count" {
+                    let count = values.iter().filter(|v| **v == value).count();
+                    return Ok(Data::Float(count as f64));
+                }
+                // -1 if the value isn't in the list.
+                let index = values.iter().position(|v| *v == value);
+                Ok(Data::Float(index.map_or(-1.0, |index| index as f64)))
+            }
+            "sum" => {
+                let Data::List(values) = self.evaluate_args("sum", args, 1)?.remove(0) else {
+                    return Err(InterpreterError::InvalidArguments("sum".to_string()));
+                };
+                values.into_iter().try_fold(Data::Float(0.0), |sum, value| {
+                    apply_op(sum, value, Operator::Plus)
+                })
+            }
+            "cumsum" | "cumprod" => {
+                let data = self.evaluate_args(name, args, 1)?.remove(0);
+                let (start, func): (f64, fn(f64, f64) -> f64) = match name {
+                    "cumsum" => (0.0, |total, value| total + value),
+                    _ => (1.0, |total, value| total * value),
+                };
+                Ok(Data::List(
+                    float_list(data, name)?
+                        .into_iter()
+                        .scan(start, |total, value| {
+                            *total = func(*total, value);
+                            Some(Data::Float(*total))
+                        })
+                        .collect(),
+                ))
+            }
+            "mean" | "median" | "stddev" => {
+                let data = self.evaluate_args(name, args, 1)?.remove(0);
+                let values = float_list(data, name)?;
+                if values.is_empty() {
+                    return Err(InterpreterError::InvalidArguments(name.to_string()));
+                }
+                Ok(Data::Float(match name {
+                    "mean" => mean(&values),
+                    "median" => median(values),
+                    _ => stddev(&values),
+                }))
+            }
+            "concat" => {
+                if args.len() != 2 {
+                    return Err(InterpreterError::InvalidArguments("concat".to_string()));
+                }
+                let (Data::List(mut left), Data::List(right)) =
+                    (self.evaluate_expr(&args[0])?, self.evaluate_expr(&args[1])?)
+                else {
+                    return Err(InterpreterError::InvalidArguments("concat".to_string()));
+                };
+                left.extend(right);
+                Ok(Data::List(left))
+            }
+            _ => Err(InterpreterError::UndefinedFunction(name.to_string())),
+        }
+    }
 
-                        let data = self.evaluate_expr(&right)?;
-                        self.variables.insert(name.clone(), data);
+    /// Fails if strict finite mode is enabled and `data` contains `inf` or `NaN`.
+    fn check_finite(&self, data: Data) -> InterpreterResult<Data> {
+        if self.strict_finite {
+            let mut values = Vec::new();
+            flatten(data.clone(), &mut values);
+            for value in values {
+                if let Data::Float(value) = value {
+                    if !value.is_finite() {
+                        return Err(InterpreterError::NonFinite(value));
                     }
                 }
-                _ => unreachable!("Some error!"),
             }
+        }
+
+        Ok(data)
+    }
+
+    /// Evaluates `expr`. This recurses for every nested expression and function call, so arms that
+    /// need locals are kept in their own functions to keep each level's stack frame small.
+    fn evaluate_expr(&self, expr: &Expr) -> InterpreterResult<Data> {
+        match expr {
+            Expr::Ident(name) => {
+                if let Some(data) = self.get_variable(name) {
+                    Ok(data.clone())
+                } else {
+                    Err(InterpreterError::UndefinedVariable(name.to_string()))
+                }
+            }
+            Expr::FloatLiteral(value) => {
+                Ok(Data::Float(value.parse().unwrap_or_else(|_| {
+                    unreachable!("Invalid value: {:?}", value)
+                })))
+            }
+            Expr::Expr(left, op, right) => self.evaluate_operation(left, op, right),
+            Expr::Comparison(first, chain) => self.evaluate_comparison(first, chain),
+            Expr::Conditional(condition, then, otherwise) => {
+                if is_truthy(self.evaluate_expr(condition)?)? {
+                    self.evaluate_expr(then)
+                } else {
+                    self.evaluate_expr(otherwise)
+                }
+            }
+            Expr::StringLiteral(value) => Ok(Data::Str(value.to_string())),
+            Expr::NegFloatLiteral(value) => {
+                let value_f64: f64 = value
+                    .parse()
+                    .unwrap_or_else(|_| unreachable!("Invalid value: {:?}", value));
+                Ok(Data::Float(-value_f64))
+            }
+            Expr::FunctionCall(name, args) => {
+                if self.functions.contains_key(name) {
+                    self.call_function(name, args)
+                } else {
+                    self.call_builtin(name, args)
+                        .and_then(|data| self.check_finite(data))
+                }
+            }
+            Expr::List(exprs) => self.evaluate_list(exprs),
+            Expr::Comprehension(body, name, list, filter) => {
+                self.evaluate_comprehension(body, name, list, filter.as_deref())
+            }
+            Expr::Index(list, index) => self.evaluate_index(list, index),
+            Expr::Lambda(..) => Err(InterpreterError::UnexpectedLambda),
+        }
+    }
+
+    /// Evaluates both operands of a binary operation and applies `op` to them.
+    #[inline(never)]
+    fn evaluate_operation(
+        &self,
+        left: &Expr,
+        op: &Operator,
+        right: &Expr,
+    ) -> InterpreterResult<Data> {
+        let left = self.evaluate_expr(left)?;
+        let right = self.evaluate_expr(right)?;
+        self.check_finite(apply_op(left, right, op.clone())?)
+    }
+
+    /// Evaluates a chain of comparisons. Every operand is evaluated once, from left to right,
+    /// stopping at the first comparison that is false. Comparing lists yields element-wise
+    /// results, which are combined by multiplying them.
+    #[inline(never)]
+    fn evaluate_comparison(
+        &self,
+        first: &Expr,
+        chain: &[(Operator, Expr)],
+    ) -> InterpreterResult<Data> {
+        let mut left = self.evaluate_expr(first)?;
+        let mut out = Data::from(true);
+        for (op, expr) in chain {
+            let right = self.evaluate_expr(expr)?;
+            out = apply_op(
+                out,
+                apply_op(left, right.clone(), op.clone())?,
+                Operator::Multi,
+            )?;
+            if out == Data::from(false) {
+                break;
+            }
+            left = right;
+        }
+
+        Ok(out)
+    }
+
+    /// Evaluates every element of a list literal.
+    #[inline(never)]
+    fn evaluate_list(&self, exprs: &[Expr]) -> InterpreterResult<Data> {
+        let vals = exprs
+            .iter()
+            .map(|expr| self.evaluate_expr(expr))
+            .collect::<InterpreterResult<Vec<Data>>>()?;
+
+        Ok(Data::List(vals))
+    }
+
+    /// Evaluates `[body for name in list if filter]`.
+    #[inline(never)]
+    fn evaluate_comprehension(
+        &self,
+        body: &Expr,
+        name: &str,
+        list: &Expr,
+        filter: Option<&Expr>,
+    ) -> InterpreterResult<Data> {
+        let Data::List(values) = self.evaluate_expr(list)? else {
+            return Err(InterpreterError::ExpectedList);
+        };
+        // Each element is substituted for the bound variable, like a function argument.
+        let mut vals = Vec::new();
+        for value in values {
+            self.step()?;
+            let bound = (vec![name.to_string()], vec![Expr::from(value)]);
+            if let Some(filter) = filter {
+                let filter = self.transform_fn_expr(bound.clone(), filter)?;
+                if !is_truthy(self.evaluate_expr(&filter)?)? {
+                    continue;
+                }
+            }
+            let body = self.transform_fn_expr(bound, body)?;
+            vals.push(self.evaluate_expr(&body)?);
+        }
+
+        Ok(Data::List(vals))
+    }
+
+    /// Evaluates `list[index]`.
+    #[inline(never)]
+    fn evaluate_index(&self, list: &Expr, index: &Expr) -> InterpreterResult<Data> {
+        let Data::List(mut values) = self.evaluate_expr(list)? else {
+            return Err(InterpreterError::ExpectedList);
+        };
+        let index = match self.evaluate_expr(index)? {
+            Data::Float(index) => index,
+            data => return Err(InterpreterError::ExpectedNumber(data.to_string())),
+        };
+        let len = values.len();
+        let i = list_index(index, len)?;
+        if i == len {
+            return Err(InterpreterError::IndexOutOfRange(index, len));
+        }
+        Ok(values.swap_remove(i))
+    }
+
+    fn format_data(&self, data: &Data) -> String {
+        let rounded;
+        let data = match self.precision {
+            Some(precision) => {
+                rounded = data.rounded(precision);
+                &rounded
+            }
+            None => data,
+        };
+
+        match self.output_format {
+            OutputFormat::Plain => data.to_string(),
+            OutputFormat::Json => data.to_json(),
+        }
+    }
+
+    fn clean_scope(&mut self, scope: Scope) {
+        for name in &scope {
+            self.variables.remove(name);
+            self.functions.remove(name);
+        }
+    }
+
+    fn function_exits(&self, name: &str) -> bool {
+        BUILTIN_FUNCTIONS.contains(&name) || self.functions.contains_key(name)
+    }
+
+    /// Returns the names of all built-in functions and constants and of the user-defined
+    /// functions, sorted.
+    pub fn available_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_FUNCTIONS
+            .iter()
+            .chain(CONSTANTS)
+            .map(|name| name.to_string())
+            .chain(self.functions.keys().cloned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Runs `block` once for every set of values produced by `iterations`, binding them to the
+    /// loop variables `names`. The loop variables live in their own frame: variables with the
+    /// same names are hidden during the loop and restored afterwards, and since the loop
+    /// variables are defined, the body can't declare variables with their names.
+    fn run_loop(
+        &mut self,
+        names: &[String],
+        iterations: impl Iterator<Item = Vec<Data>>,
+        block: &[Parsed],
+    ) -> InterpreterResult<()> {
+        let shadowed: Vec<(String, Option<Data>)> = names
+            .iter()
+            .map(|name| (name.clone(), self.variables.remove(name)))
+            .collect();
+
+        let mut result = Ok(());
+        for values in iterations {
+            result = self.step();
+            if result.is_err() {
+                break;
+            }
+            for (name, value) in names.iter().zip(values) {
+                self.variables.insert(name.clone(), value);
+            }
+            result = self
+                .execute_block(block.to_vec())
+                .map(|scope| self.clean_scope(scope));
+            if result.is_err() {
+                break;
+            }
+        }
+
+        for (name, data) in shadowed {
+            self.variables.remove(&name);
+            if let Some(data) = data {
+                self.variables.insert(name, data);
+            }
+        }
+
+        result
+    }
+
+    /// Binds the elements of the list `values` to the names in `targets`, recursing into nested
+    /// lists.
+    fn destructure(
+        &mut self,
+        targets: &[Expr],
+        values: Data,
+        scope: &mut Scope,
+    ) -> InterpreterResult<()> {
+        let Data::List(values) = values else {
+            return Err(InterpreterError::ExpectedList);
+        };
+        if targets.len() != values.len() {
+            return Err(InterpreterError::DestructuringLength(
+                values.len(),
+                targets.len(),
+            ));
+        }
+        for (target, data) in targets.iter().zip(values) {
+            let name = match target {
+                // `_` skips the element.
+                Expr::Ident(name) if name == "_" => continue,
+                Expr::Ident(name) => name,
+                Expr::List(targets) => {
+                    self.destructure(targets, data, scope)?;
+                    continue;
+                }
+                _ => return Err(InterpreterError::InvalidDestructuringTarget),
+            };
+
+            if self.get_variable(name).is_some() {
+                return Err(InterpreterError::RedeclaredVariable(name.clone(), None));
+            }
+
+            self.variables.insert(name.clone(), data);
+            scope.push(name.clone());
+        }
+
+        Ok(())
+    }
+
+    fn execute_block(&mut self, block: Vec<Parsed>) -> InterpreterResult<Scope> {
+        let mut current = 0usize;
+        let mut scope: Scope = Vec::new();
+        while block.get(current).is_some() {
+            self.step()?;
+            let parsed = block.get(current).unwrap().clone();
+            let loc = parsed.span().cloned();
+            self.execute_statement(parsed, &mut scope)
+                .map_err(|err| err.at(loc.as_ref()))?;
             current += 1;
         }
 
         Ok(scope)
     }
 
-    pub fn interpret(&mut self) -> Result<()> {
-        let scope = self.execute_block(self.parsed.clone())?;
+    /// Executes a single statement, adding the names it declares to `scope`.
+    fn execute_statement(&mut self, parsed: Parsed, scope: &mut Scope) -> InterpreterResult<()> {
+        match parsed {
+            Parsed::Declaration(idents, expr) => {
+                let mut names: Vec<String> = Vec::new();
+                for ident in idents {
+                    let Token(TokenType::Ident(name), loc) = ident else {
+                        return Err(InterpreterError::ExpectedIdent);
+                    };
+                    if self.get_variable(&name).is_some() || names.contains(&name) {
+                        return Err(InterpreterError::RedeclaredVariable(name, Some(loc)));
+                    }
+                    names.push(name);
+                }
+                let value = self.evaluate_expr(&expr)?;
+                for name in names {
+                    self.variables.insert(name.to_string(), value.clone());
+                    scope.push(name);
+                }
+            }
+            Parsed::PrintExpr(expr) => {
+                let value = self.evaluate_expr(&expr)?;
+                let out = self.format_data(&value);
+                writeln!(self.writer, "{}", out)?;
+            }
+            Parsed::Print(exprs) => {
+                let out = exprs
+                    .iter()
+                    .map(|expr| Ok(self.format_data(&self.evaluate_expr(expr)?)))
+                    .collect::<InterpreterResult<Vec<String>>>()?;
+                writeln!(self.writer, "{}", out.join(" "))?;
+            }
+            Parsed::Write(expr) => {
+                let value = self.evaluate_expr(&expr)?;
+                let out = self.format_data(&value);
+                write!(self.writer, "{}", out)?;
+                self.writer.flush()?;
+            }
+            Parsed::FunctionDecleration(Token(TokenType::Ident(f), loc), parameters, expr) => {
+                if self.function_exits(&f) {
+                    return Err(InterpreterError::RedeclaredFunction(f, loc));
+                }
+                let mut rest = None;
+                let mut function_parameters = Vec::new();
+                for Parameter {
+                    name,
+                    annotation,
+                    default,
+                    rest: is_rest,
+                } in parameters
+                {
+                    let Token(TokenType::Ident(name), _) = name else {
+                        unreachable!("Internal error!");
+                    };
+                    let parameter = FunctionParameter {
+                        name,
+                        default,
+                        annotation,
+                    };
+                    if is_rest {
+                        rest = Some(parameter);
+                    } else {
+                        function_parameters.push(parameter);
+                    }
+                }
+                self.functions.insert(
+                    f.to_string(),
+                    Function {
+                        parameters: function_parameters,
+                        rest,
+                        body: expr.node,
+                    },
+                );
+                scope.push(f.to_string());
+            }
+            Parsed::FromLoop(min_expr, max_expr, ident_expr, step_expr, block) => {
+                let min = match self.evaluate_expr(&min_expr)? {
+                    Data::Float(value) => value,
+                    _ => return Err(InterpreterError::InvalidLoopBounds),
+                };
+                let max = match self.evaluate_expr(&max_expr)? {
+                    Data::Float(value) => value,
+                    _ => return Err(InterpreterError::InvalidLoopBounds),
+                };
+                let step = match self.evaluate_expr(&step_expr)? {
+                    Data::Float(value) => value,
+                    _ => return Err(InterpreterError::InvalidLoopBounds),
+                };
+                let Expr::Ident(name) = ident_expr.node else {
+                    return Err(InterpreterError::ExpectedIdent);
+                };
+                // A step of 0 (or NaN) would never move the counter at all.
+                let reachable = (step > 0.0 && min <= max) || (step < 0.0 && min >= max);
+                if !reachable {
+                    return Err(InterpreterError::UnreachableLoopEnd(step, max, min));
+                }
+                let counter = std::iter::successors(Some(min), |i| Some(i + step))
+                    .take_while(|i| (step > 0.0 && *i <= max) || (step < 0.0 && *i >= max))
+                    .map(|i| vec![Data::Float(i)]);
+                self.run_loop(&[name], counter, &block)?;
+            }
+            Parsed::Block(block) => {
+                let scope = self.execute_block(block)?;
+                self.clean_scope(scope);
+            }
+            Parsed::ForLoop(ident_expr, list_expr, step_expr, index_expr, block) => {
+                let list = match self.evaluate_expr(&list_expr)? {
+                    Data::List(datas) => datas,
+                    _ => return Err(InterpreterError::ExpectedList),
+                };
+                let Expr::Ident(name) = ident_expr.node else {
+                    return Err(InterpreterError::ExpectedIdent);
+                };
+                let index = match index_expr.map(|index| index.node) {
+                    Some(Expr::Ident(index)) => Some(index),
+                    Some(_) => return Err(InterpreterError::ExpectedIdent),
+                    None => None,
+                };
+                let step = match self.evaluate_expr(&step_expr)? {
+                    Data::Float(value) if value >= 1.0 && value.fract() == 0.0 => value as usize,
+                    _ => return Err(InterpreterError::InvalidForStep),
+                };
+                let names: Vec<String> = std::iter::once(name).chain(index).collect();
+                let elements = list
+                    .into_iter()
+                    .enumerate()
+                    .step_by(step)
+                    .map(|(i, data)| vec![data, Data::Float(i as f64)]);
+                self.run_loop(&names, elements, &block)?;
+            }
+            Parsed::Destructuring(left, right) => {
+                let Expr::List(left_exprs) = left.node else {
+                    return Err(InterpreterError::InvalidDestructuring);
+                };
+                let values = self.evaluate_expr(&right)?;
+                self.destructure(&left_exprs, values, scope)?;
+            }
+            Parsed::Assert(Token(_, loc), expr, message) => {
+                if !is_truthy(self.evaluate_expr(&expr)?)? {
+                    let message = match message {
+                        Some(message) => Some(self.evaluate_expr(&message)?.to_string()),
+                        None => None,
+                    };
+                    return Err(InterpreterError::AssertionFailed(loc, message));
+                }
+            }
+            Parsed::Import(_, _, path) => {
+                let file = canonicalize(&path)
+                    .map_err(|err| InterpreterError::ImportRead(path.clone(), err))?;
+                if self.imports.contains(&file) {
+                    return Err(InterpreterError::ImportCycle(path));
+                }
+                let content = read_to_string(&file)
+                    .map_err(|err| InterpreterError::ImportRead(path.clone(), err))?;
+                let parsed = Lexer::new(path, content)
+                    .tokenize()
+                    .and_then(|tokens| Parser::new(tokens).parse())
+                    .map_err(|err| InterpreterError::Import(Box::new(err)))?;
+
+                // The imported declarations belong to the scope of the import.
+                self.imports.push(file);
+                let imported = self.execute_block(parsed);
+                self.imports.pop();
+                scope.extend(imported?);
+            }
+            _ => unreachable!("Some error!"),
+        }
+
+        Ok(())
+    }
+
+    /// Defines the variable `name`, replacing any previous value.
+    pub fn set_variable(&mut self, name: String, data: Data) {
+        self.variables.insert(name, data);
+    }
+
+    /// Removes all user-defined variables and functions. Built-ins are unaffected.
+    pub fn reset(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+    }
+
+    /// Returns the names of all user-defined variables, sorted.
+    pub fn variable_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.variables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the names of all user-defined functions, sorted.
+    pub fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Executes `parsed` without cleaning up its top-level declarations, so that they stay
+    /// available to later calls. Used by the REPL.
+    pub fn execute(&mut self, parsed: Vec<Parsed>) -> Result<(), MathyError> {
+        self.execute_block(parsed)?;
+
+        Ok(())
+    }
+
+    /// Like [`Interpreter::interpret`], but if the last statement is a bare expression, its value
+    /// is returned instead of printed. Earlier expressions are still printed.
+    pub fn evaluate(&mut self) -> Result<Option<Data>, MathyError> {
+        let mut parsed = self.parsed.clone();
+        let last = match parsed.last() {
+            Some(Parsed::PrintExpr(_)) => parsed.pop(),
+            _ => None,
+        };
+        let scope = self.execute_block(parsed)?;
+        let value = match last {
+            Some(Parsed::PrintExpr(expr)) => self
+                .evaluate_expr(&expr)
+                .map(Some)
+                .map_err(|err| err.at(Some(&expr.span))),
+            _ => Ok(None),
+        };
+        self.clean_scope(scope);
+
+        Ok(value?)
+    }
+
+    pub fn interpret(&mut self) -> Result<(), MathyError> {
+        let parsed = self.parsed.clone();
+        let scope = self.execute_block(parsed)?;
         self.clean_scope(scope);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects everything written to it, so that tests can check printed output.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `src` and returns what it printed.
+    fn run(src: &str) -> Result<String, MathyError> {
+        run_with(src, |_| {})
+    }
+
+    /// Like [`run`], but lets `configure` change the settings of the interpreter first.
+    fn run_with(src: &str, configure: impl FnOnce(&mut Interpreter)) -> Result<String, MathyError> {
+        let mut interpreter = Interpreter::new(crate::parse(src)?);
+        configure(&mut interpreter);
+        let output = Output::default();
+        interpreter.set_writer(Box::new(output.clone()));
+        interpreter.interpret()?;
+        let printed = output.0.lock().unwrap().clone();
+        Ok(String::from_utf8(printed).unwrap())
+    }
+
+    /// Runs `src` and returns the runtime error it fails with, without its statement location.
+    fn run_err(src: &str) -> InterpreterError {
+        match run(src) {
+            Err(MathyError::Interpreter(InterpreterError::At(err, _))) => *err,
+            Err(MathyError::Interpreter(err)) => err,
+            out => panic!("expected a runtime error, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn matrix_solve_eliminates_below_the_pivot() {
+        assert_eq!(
+            run("matrix_solve([[0, 1, 2], [1, 0, 1], [2, 1, 0]], [8, 4, 4])").unwrap(),
+            "[1, 2, 3]\n"
+        );
+        assert!(matches!(
+            run_err("matrix_solve([[1, 2], [2, 4]], [1, 2])"),
+            InterpreterError::SingularMatrix
+        ));
+    }
+
+    #[test]
+    fn median_of_even_and_odd_lengths() {
+        assert_eq!(run("median([3, 1, 2])").unwrap(), "2\n");
+        assert_eq!(run("median([4, 1, 3, 2])").unwrap(), "2.5\n");
+        assert!(matches!(
+            run_err("median([])"),
+            InterpreterError::InvalidArguments(name) if name == "median"
+        ));
+    }
+
+    #[test]
+    fn from_loop_rejects_unreachable_ends() {
+        assert_eq!(
+            run("from 3 to 1 as i with step -1 {\n    i\n}").unwrap(),
+            "3\n2\n1\n"
+        );
+        for src in [
+            "from 0 to 3 as i with step 0 {\n}",
+            "from 0 to 3 as i with step -1 {\n}",
+            "from 3 to 0 as i {\n}",
+        ] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::UnreachableLoopEnd(..)
+            ));
+        }
+    }
+
+    #[test]
+    fn default_and_rest_parameters() {
+        assert_eq!(
+            run("h(x, y = x + 1) = x * y\nh(2)\nh(2, 5)").unwrap(),
+            "6\n10\n"
+        );
+        assert_eq!(
+            run("avg(nums...) = sum(nums) / len(nums)\navg(1, 2, 3)").unwrap(),
+            "2\n"
+        );
+        assert!(matches!(
+            run_err("h(x, y = 1) = x + y\nh()"),
+            InterpreterError::InvalidArguments(name) if name == "h"
+        ));
+    }
+
+    #[test]
+    fn errors_in_substituted_lists_are_reported() {
+        for src in [
+            "f(x) = [x, y]\nf(1)",
+            "[[x, y] for x in [1, 2]]",
+            "map([1, 2], x -> [x, q])",
+        ] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::UndefinedVariable(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn recursion_limit_holds_on_a_default_stack() {
+        assert!(matches!(
+            crate::eval("f(x) = f(x)\nf(1)"),
+            Err(MathyError::Interpreter(err))
+                if matches!(err.inner(), InterpreterError::RecursionLimit(name) if name == "f")
+        ));
+        assert!(matches!(
+            crate::eval("f(x) = x <= 0 ? 0 : 1 + f(x - 1)\nf(199)"),
+            Ok(Some(Data::Float(value))) if value == 199.0
+        ));
+    }
+
+    #[test]
+    fn fill_checks_its_count() {
+        assert_eq!(run("fill(0, 3)").unwrap(), "[0, 0, 0]\n");
+        assert_eq!(run("fill(1, 0)").unwrap(), "[]\n");
+        assert!(matches!(
+            run_err("fill(0, 10 ^ 19)"),
+            InterpreterError::IterationLimit(name) if name == "fill"
+        ));
+        for src in ["fill(0, -1)", "fill(0, 2.5)", "fill(0, nan)"] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::InvalidArguments(name) if name == "fill"
+            ));
+        }
+    }
+
+    #[test]
+    fn linspace_checks_its_count() {
+        assert_eq!(run("linspace(0, 1, 3)").unwrap(), "[0, 0.5, 1]\n");
+        assert!(matches!(
+            run_err("linspace(0, 1, 10 ^ 19)"),
+            InterpreterError::IterationLimit(name) if name == "linspace"
+        ));
+        for src in [
+            "linspace(0, 1, 1)",
+            "linspace(0, 1, 2.5)",
+            "linspace(0, 1, nan)",
+        ] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::InvalidArguments(name) if name == "linspace"
+            ));
+        }
+    }
+
+    /// Runs `src` with a step limit of `limit` and returns the runtime error it fails with.
+    fn run_limited(src: &str, limit: usize) -> InterpreterError {
+        let mut interpreter = Interpreter::new(crate::parse(src).unwrap());
+        interpreter.set_writer(Box::new(Output::default()));
+        interpreter.set_step_limit(Some(limit));
+        match interpreter.interpret() {
+            Err(MathyError::Interpreter(InterpreterError::At(err, _))) => *err,
+            Err(MathyError::Interpreter(err)) => err,
+            out => panic!("expected a runtime error, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn step_limit_counts_produced_elements() {
+        for src in [
+            "from 0 to 10 ^ 9 as i {\n    i\n}",
+            "f(n) = f(n + 1)\nf(1)",
+            "fill(0, 10 ^ 11)",
+            "linspace(0, 1, 1000)",
+            "[x for x in fill(0, 1000)]",
+            "map(fill(0, 90), x -> x + 1)",
+        ] {
+            assert!(matches!(
+                run_limited(src, 100),
+                InterpreterError::StepLimitExceeded(100)
+            ));
+        }
+    }
+
+    #[test]
+    fn trigonometric_functions_check_their_arity() {
+        assert_eq!(run("sin(0)\ncos([0, 0])").unwrap(), "0\n[1, 1]\n");
+        for src in ["sin()", "cos()", "tan()", "tan(1, 2)"] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::InvalidArguments(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn operators_need_lists_of_the_same_length() {
+        assert_eq!(
+            run("[1, 2, 3] * 2\n[1, 2] - [1, 1]").unwrap(),
+            "[2, 4, 6]\n[0, 1]\n"
+        );
+        for src in ["[1, 2] + [1, 2, 3]", "[1, 2] - [1]", "[2] ^ [1, 2, 3]"] {
+            assert!(matches!(run_err(src), InterpreterError::InvalidListLength));
+        }
+    }
+
+    #[test]
+    fn two_argument_built_ins_broadcast() {
+        for (src, expected) in [
+            ("pow(2, 3)", "8"),
+            ("pow([1, 2, 3], 2)", "[1, 4, 9]"),
+            ("pow(2, [1, 2, 3])", "[2, 4, 8]"),
+            ("pow([1, 2], [3, 2])", "[1, 4]"),
+            ("pow([2], [1, 2, 3])", "[2, 4, 8]"),
+            ("pow([[1, 2], [3]], 2)", "[[1, 4], [9]]"),
+            ("atan2(0, [1, -1])", "[0, 3.141592653589793]"),
+            ("atan2([0, 1], [1])", "[0, 0.7853981633974483]"),
+            ("clamp([1, 5, 9], [2], 8)", "[2, 5, 8]"),
+            ("clamp(5, [0, 6], [4])", "[4, 4]"),
+        ] {
+            assert_eq!(run(src).unwrap(), format!("{}\n", expected), "{}", src);
+        }
+        for src in ["pow([1, 2], [1, 2, 3])", "atan2([1, 2], [1, 2, 3])"] {
+            assert!(matches!(run_err(src), InterpreterError::InvalidListLength));
+        }
+    }
+
+    #[test]
+    fn runtime_errors_point_at_their_statement() {
+        let err = run("x = 1\nfrom 0 to 2 as i {\n    x + y\n}").unwrap_err();
+        let loc = err
+            .location()
+            .expect("runtime errors should have a location");
+        assert_eq!((loc.2, loc.1), (3, 5));
+        assert!(err
+            .to_string()
+            .ends_with("Undefined variable: \"y\" at <input>:3:5"));
+
+        // Errors that have a location of their own keep it.
+        let err = run("x = 1\nx = 2").unwrap_err();
+        assert_eq!(err.location().map(|loc| loc.2), Some(2));
+        assert!(matches!(
+            err,
+            MathyError::Interpreter(InterpreterError::RedeclaredVariable(..))
+        ));
+    }
+
+    #[test]
+    fn concat_joins_flat_and_nested_lists() {
+        assert_eq!(run("concat([1, 2], [3, 4])").unwrap(), "[1, 2, 3, 4]\n");
+        assert_eq!(run("concat([1], [[2, 3], 4])").unwrap(), "[1, [2, 3], 4]\n");
+    }
+
+    #[test]
+    fn collatz_generates_the_sequence() {
+        assert_eq!(
+            run("collatz(6)").unwrap(),
+            "[6, 3, 10, 5, 16, 8, 4, 2, 1]\n"
+        );
+        assert_eq!(
+            run("print len(collatz(27)), last(collatz(27))").unwrap(),
+            "112 1\n"
+        );
+        for src in ["collatz(0)", "collatz(2.5)"] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::InvalidArguments(name) if name == "collatz"
+            ));
+        }
+    }
+
+    #[test]
+    fn zip_pairs_lists_of_the_same_length() {
+        assert_eq!(run("zip([1, 2], [3, 4])").unwrap(), "[[1, 3], [2, 4]]\n");
+        assert!(matches!(
+            run_err("zip([1, 2, 3], [4])"),
+            InterpreterError::InvalidListLength
+        ));
+    }
+
+    #[test]
+    fn dot_product() {
+        assert_eq!(run("dot([1, 2, 3], [4, 5, 6])").unwrap(), "32\n");
+        assert!(matches!(
+            run_err("dot([1, 2], [1, 2, 3])"),
+            InterpreterError::InvalidListLength
+        ));
+        assert!(matches!(
+            run_err("dot([[1], 2], [1, 2])"),
+            InterpreterError::InvalidArguments(name) if name == "dot"
+        ));
+    }
+
+    #[test]
+    fn matrix_solve_solves_small_systems() {
+        assert_eq!(
+            run("matrix_solve([[2, 1], [1, 3]], [3, 5])").unwrap(),
+            "[0.8, 1.4]\n"
+        );
+        assert_eq!(
+            run("matrix_solve([[1, 1, 1], [0, 2, 5], [2, 5, -1]], [6, -4, 27])").unwrap(),
+            "[5, 3, -2]\n"
+        );
+        for src in [
+            "matrix_solve([[1, 2], [3, 4]], [1])",
+            "matrix_solve([[1, 2]], [1])",
+        ] {
+            assert!(matches!(run_err(src), InterpreterError::InvalidListLength));
+        }
+    }
+
+    #[test]
+    fn reset_clears_user_state_but_keeps_built_ins() {
+        let mut interpreter = Interpreter::new(Vec::new());
+        interpreter.set_writer(Box::new(Output::default()));
+        interpreter
+            .execute(crate::parse("y = 1\ng(a) = a\nb = g(y)").unwrap())
+            .unwrap();
+        interpreter.set_variable("z".to_string(), Data::Float(3.0));
+        assert_eq!(interpreter.variable_names(), ["b", "y", "z"]);
+        assert_eq!(interpreter.function_names(), ["g"]);
+        assert!(interpreter.available_names().contains(&"sin".to_string()));
+
+        interpreter.reset();
+        assert!(interpreter.variable_names().is_empty());
+        assert!(interpreter.function_names().is_empty());
+        interpreter
+            .execute(crate::parse("w = sin(PI / 2)").unwrap())
+            .unwrap();
+        match interpreter.execute(crate::parse("v = y").unwrap()) {
+            Err(MathyError::Interpreter(err)) => assert!(matches!(
+                err.inner(),
+                InterpreterError::UndefinedVariable(name) if name == "y"
+            )),
+            out => panic!("expected an undefined variable, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn empty_lists() {
+        assert_eq!(run("[]").unwrap(), "[]\n");
+        assert_eq!(run("x = []\nprint len(x), sum(x)").unwrap(), "0 0\n");
+        assert_eq!(run("for y in [] {\n    y\n}\n1").unwrap(), "1\n");
+    }
+
+    #[test]
+    fn for_loops_run_once_per_element() {
+        for (list, expected) in [("[]", ""), ("[7]", "7"), ("[1, 2, 3]", "123")] {
+            let src = format!("for y in {} {{\n    write y\n}}", list);
+            assert_eq!(run(&src).unwrap(), expected, "{}", list);
+        }
+    }
+
+    #[test]
+    fn from_loop_counters_are_read_only() {
+        assert_eq!(
+            run("i = 9\nfrom 0 to 2 as i {\n    write i\n}\nprint \"\"\ni").unwrap(),
+            "012\n9\n"
+        );
+        assert!(matches!(
+            run_err("from 0 to 3 as i {\n    i = 5\n}"),
+            InterpreterError::RedeclaredVariable(name, Some(TokenLocation(_, 5, 2))) if name == "i"
+        ));
+    }
+
+    #[test]
+    fn mean_and_standard_deviation() {
+        assert_eq!(run("mean([1, 2, 3, 4])").unwrap(), "2.5\n");
+        assert_eq!(run("stddev([2, 4, 4, 4, 5, 5, 7, 9])").unwrap(), "2\n");
+        for name in ["mean", "stddev"] {
+            assert!(matches!(
+                run_err(&format!("{}([])", name)),
+                InterpreterError::InvalidArguments(function) if function == name
+            ));
+        }
+    }
+
+    #[test]
+    fn exp_and_pow() {
+        assert_eq!(
+            run("print exp(0), exp([0, 1]) == [1, exp(1)]").unwrap(),
+            "1 [1, 1]\n"
+        );
+        assert_eq!(
+            run("print pow(2, 10), pow([2, 3], 2)").unwrap(),
+            "1024 [4, 9]\n"
+        );
+    }
+
+    #[test]
+    fn inverse_and_hyperbolic_trigonometry() {
+        assert_eq!(
+            run("print asin(1) * 2 == PI, acos(1), atan(0)").unwrap(),
+            "1 0 0\n"
+        );
+        assert_eq!(run("print sinh(0), cosh(0), tanh(0)").unwrap(), "0 1 0\n");
+        assert_eq!(
+            run("print atan2(1, 1) * 4 == PI, atan2(0, -1) == PI").unwrap(),
+            "1 1\n"
+        );
+    }
+
+    #[test]
+    fn json_output() {
+        let printed = run_with("PI\n[1, nan]\n\"a\"", |interpreter| {
+            interpreter.set_output_format(OutputFormat::Json);
+        });
+        assert_eq!(printed.unwrap(), "3.141592653589793\n[1,null]\n\"a\"\n");
+    }
+
+    #[test]
+    fn for_loops_can_count_their_index() {
+        assert_eq!(
+            run("for y in [10, 20] with index i {\n    print i, y\n}").unwrap(),
+            "0 10\n1 20\n"
+        );
+    }
+
+    #[test]
+    fn for_loops_can_skip_elements() {
+        assert_eq!(
+            run("for y in [1, 2, 3, 4, 5] with step 2 {\n    print y\n}").unwrap(),
+            "1\n3\n5\n"
+        );
+        assert!(matches!(
+            run_err("for y in [1, 2] with step 0 {\n    y\n}"),
+            InterpreterError::InvalidForStep
+        ));
+    }
+
+    #[test]
+    fn from_loops_count_up_and_down() {
+        assert_eq!(
+            run("from 1 to 3 as i {\n    print i\n}").unwrap(),
+            "1\n2\n3\n"
+        );
+        assert_eq!(
+            run("from 0 to 10 as i with step 4 {\n    print i\n}").unwrap(),
+            "0\n4\n8\n"
+        );
+        assert_eq!(
+            run("from 3 to 1 as i with step -1 {\n    print i\n}").unwrap(),
+            "3\n2\n1\n"
+        );
+    }
+
+    #[test]
+    fn sign_and_trunc() {
+        assert_eq!(run("sign([-3, 0, 2])").unwrap(), "[-1, 0, 1]\n");
+        assert_eq!(
+            run("print trunc(-2.7), trunc([2.7, 5])").unwrap(),
+            "-2 [2, 5]\n"
+        );
+    }
+
+    #[test]
+    fn functions_compose_through_three_levels() {
+        assert_eq!(
+            run("f(x) = x + 1\ng(x) = f(x) * 2\nh(x) = g(f(x))\nh(1)").unwrap(),
+            "6\n"
+        );
+    }
+
+    #[test]
+    fn write_prints_without_a_newline() {
+        assert_eq!(
+            run("write 1\nwrite \"a\"\nwrite [1, 2]\nprint 3").unwrap(),
+            "1a[1, 2]3\n"
+        );
+    }
+
+    #[test]
+    fn strings_concatenate_with_values() {
+        assert_eq!(run("x = 5\n\"x = \" + x").unwrap(), "x = 5\n");
+        assert_eq!(run("3 + \"!\"").unwrap(), "3!\n");
+        assert_eq!(run("\"n\" + [1, 2]").unwrap(), "n[1, 2]\n");
+        assert!(matches!(
+            run_err("\"a\" - 1"),
+            InterpreterError::InvalidOperands(Operator::Minus)
+        ));
+    }
+
+    #[test]
+    fn destructuring_evaluates_its_value() {
+        assert_eq!(run("[n, m] = zip([1], [2])[0]\nn + m").unwrap(), "3\n");
+        assert_eq!(
+            run("f(x) = [x, x * 2]\n[a, b] = f(3)\nprint a, b").unwrap(),
+            "3 6\n"
+        );
+        assert!(matches!(
+            run_err("[a, b] = 5"),
+            InterpreterError::ExpectedList
+        ));
+    }
+
+    #[test]
+    fn floor_division() {
+        assert_eq!(run("print 7 // 2, (-7) // 2").unwrap(), "3 -4\n");
+        assert_eq!(run("[7, 8] // 2").unwrap(), "[3, 4]\n");
+    }
+
+    #[test]
+    fn comparisons_chain() {
+        assert_eq!(
+            run("x = 5\nprint 0 <= x < 10, 0 <= x < 3, 1 < 2 > 1").unwrap(),
+            "1 0 1\n"
+        );
+        assert_eq!(run("[1, 5] < 3").unwrap(), "[1, 0]\n");
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        assert_eq!(run("x = 5\nx > 3 ? \"big\" : \"small\"").unwrap(), "big\n");
+        assert_eq!(run("x = 5\nx > 9 ? 1 : x > 4 ? 2 : 3").unwrap(), "2\n");
+    }
+
+    #[test]
+    fn first_last_and_tail() {
+        assert_eq!(
+            run("print first([1, 2, 3]), last([1, 2, 3]), tail([1, 2, 3]), tail([1])").unwrap(),
+            "1 3 [2, 3] []\n"
+        );
+        for name in ["first", "last"] {
+            assert!(matches!(
+                run_err(&format!("{}([])", name)),
+                InterpreterError::InvalidArguments(function) if function == name
+            ));
+        }
+    }
+
+    #[test]
+    fn contains_compares_whole_elements() {
+        assert_eq!(
+            run("print contains([1, 2], 3), contains([1, 2], 2), contains([[1], 2], [1])").unwrap(),
+            "0 1 1\n"
+        );
+    }
+
+    #[test]
+    fn precision_rounds_printed_numbers() {
+        let printed = run_with("print PI, 0.1 + 0.2, [1 / 3, 2]", |interpreter| {
+            interpreter.set_precision(Some(3))
+        });
+        assert_eq!(printed.unwrap(), "3.142 0.3 [0.333, 2]\n");
+    }
+
+    #[test]
+    fn comments_can_end_a_statement() {
+        assert_eq!(
+            run("x = 5 # five\nprint x # shown\nf(y) = y # id\nf(x)").unwrap(),
+            "5\n5\n"
+        );
+    }
+
+    #[test]
+    fn flatten_collapses_nested_lists() {
+        assert_eq!(
+            run("flatten([[1, 2], [3, [4, 5]]])").unwrap(),
+            "[1, 2, 3, 4, 5]\n"
+        );
+        assert_eq!(
+            run("print flatten([1, 2]), flatten([[], []])").unwrap(),
+            "[1, 2] []\n"
+        );
+        assert!(matches!(
+            run_err("flatten(5)"),
+            InterpreterError::InvalidArguments(name) if name == "flatten"
+        ));
+    }
+
+    #[test]
+    fn slice_takes_a_half_open_range() {
+        assert_eq!(
+            run("print slice([0, 1, 2, 3, 4], 1, 3), slice([1, 2, 3], 1), slice([1, 2], 2, 2)")
+                .unwrap(),
+            "[1, 2] [2, 3] []\n"
+        );
+        assert!(matches!(
+            run_err("slice([1, 2], 3, 3)"),
+            InterpreterError::IndexOutOfRange(index, 2) if index == 3.0
+        ));
+        assert!(matches!(
+            run_err("slice([1, 2], 2, 1)"),
+            InterpreterError::InvalidArguments(name) if name == "slice"
+        ));
+    }
+
+    #[test]
+    fn loop_variables_shadow_and_cannot_be_redeclared() {
+        assert_eq!(
+            run("x = 7\nfor x in [1, 2] {\n    print x\n}\nprint x").unwrap(),
+            "1\n2\n7\n"
+        );
+        assert!(matches!(
+            run_err("for i in [1, 2] {\n    i = 3\n}"),
+            InterpreterError::RedeclaredVariable(name, _) if name == "i"
+        ));
+        assert_eq!(
+            run("for i in [1, 2] {\n    j = i * 10\n    print j\n}\nprint defined(\"i\"), defined(\"j\")")
+                .unwrap(),
+            "10\n20\n0 0\n"
+        );
+    }
+
+    #[test]
+    fn expressions_continue_after_a_trailing_operator() {
+        assert_eq!(run("x = 1 +\n    2 *\n    3\nprint x").unwrap(), "7\n");
+        assert_eq!(run("[1, 2] ==\n[1, 2]").unwrap(), "[1, 1]\n");
+    }
+
+    #[test]
+    fn round_rounds_each_element() {
+        assert_eq!(
+            run("print round([1.25, 2.5, -1.5]), round([PI, 2.718], 2), round(PI, 2)").unwrap(),
+            "[1, 3, -2] [3.14, 2.72] 3.14\n"
+        );
+    }
+
+    #[test]
+    fn gcd_and_lcm() {
+        assert_eq!(
+            run("print gcd(12, 18), lcm(4, 6), gcd(0, 5)").unwrap(),
+            "6 12 5\n"
+        );
+        assert_eq!(
+            run("print gcd([12, 9], 6), lcm([2, 3], [5, 7])").unwrap(),
+            "[6, 3] [10, 21]\n"
+        );
+    }
+
+    #[test]
+    fn factorial_of_whole_numbers() {
+        assert_eq!(
+            run("print factorial(0), factorial(5), factorial(2.5), factorial([3, 4])").unwrap(),
+            "1 120 2 [6, 24]\n"
+        );
+        assert_eq!(
+            run("print factorial(171), factorial(nan)").unwrap(),
+            "inf nan\n"
+        );
+        assert!(matches!(
+            run_err("factorial(-1)"),
+            InterpreterError::InvalidArguments(name) if name == "factorial"
+        ));
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(run("print 6 & 3, 5 | 2, 6 xor 3").unwrap(), "2 7 5\n");
+        assert_eq!(run("[1, 2] | 4").unwrap(), "[5, 6]\n");
+    }
+
+    #[test]
+    fn shift_operators() {
+        assert_eq!(run("print 1 << 4, 256 >> 2").unwrap(), "16 64\n");
+        assert_eq!(run("[1, 2] << 1").unwrap(), "[2, 4]\n");
+    }
+
+    #[test]
+    fn assertions() {
+        assert_eq!(run("assert 1 + 1 == 2\nprint \"ok\"").unwrap(), "ok\n");
+        assert!(matches!(
+            run_err("assert 1 > 2, \"one is not more than two\"\nprint \"never\""),
+            InterpreterError::AssertionFailed(TokenLocation(_, 1, 1), Some(message))
+                if message == "one is not more than two"
+        ));
+        assert!(matches!(
+            run_err("x = 0\nassert x"),
+            InterpreterError::AssertionFailed(TokenLocation(_, 1, 2), None)
+        ));
+    }
+
+    #[test]
+    fn comprehensions_map_each_element() {
+        assert_eq!(run("[x ^ 2 for x in [1, 2, 3]]").unwrap(), "[1, 4, 9]\n");
+        assert_eq!(run("[x for x in []]").unwrap(), "[]\n");
+        assert!(matches!(
+            run_err("[x for x in 5]"),
+            InterpreterError::ExpectedList
+        ));
+    }
+
+    #[test]
+    fn comprehensions_filter_elements() {
+        assert_eq!(
+            run("[x * 2 for x in [1, 2, 3] if x != 2]").unwrap(),
+            "[2, 6]\n"
+        );
+        assert_eq!(run("[x for x in [1, 2] if x > 5]").unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn chained_assignment_binds_every_target() {
+        assert_eq!(run("a = b = 5\nprint a + b").unwrap(), "10\n");
+        assert_eq!(run("c = d = [1]\nprint c, d").unwrap(), "[1] [1]\n");
+        assert!(matches!(
+            run_err("x = 1\ny = x = 2"),
+            InterpreterError::RedeclaredVariable(name, _) if name == "x"
+        ));
+    }
+
+    #[test]
+    fn strict_finite_rejects_overflow() {
+        assert_eq!(run("print 10 ^ 400, 1 / 0").unwrap(), "inf inf\n");
+        for src in ["10 ^ 400", "1 / 0", "x = [1, 10 ^ 400]"] {
+            let out = run_with(src, |interpreter| interpreter.set_strict_finite(true));
+            assert!(
+                matches!(
+                    &out,
+                    Err(MathyError::Interpreter(err))
+                        if matches!(err.inner(), InterpreterError::NonFinite(value) if value.is_infinite())
+                ),
+                "{:?} gave {:?}",
+                src,
+                out
+            );
+        }
+    }
+
+    #[test]
+    fn import_errors_point_at_the_import() {
+        let err = run("x = 1\nimport \"no such file.mth\"").unwrap_err();
+        assert_eq!(err.location().map(|loc| (loc.1, loc.2)), Some((1, 2)));
+        assert!(matches!(
+            err,
+            MathyError::Interpreter(err) if matches!(err.inner(), InterpreterError::ImportRead(..))
+        ));
+    }
+
+    #[test]
+    fn count_and_index_of() {
+        assert_eq!(
+            run("print count([1, 2, 2, 3], 2), count([], 1), count([[1], 1], [1])").unwrap(),
+            "2 0 1\n"
+        );
+        assert_eq!(
+            run("print index_of([4, 5, 6], 6), index_of([5], 9)").unwrap(),
+            "2 -1\n"
+        );
+        for name in ["count", "index_of"] {
+            assert!(matches!(
+                run_err(&format!("{}(5, 1)", name)),
+                InterpreterError::InvalidArguments(function) if function == name
+            ));
+        }
+    }
+
+    #[test]
+    fn map_filter_and_reduce_take_lambdas() {
+        assert_eq!(run("map([1, 2, 3], x -> x + 1)").unwrap(), "[2, 3, 4]\n");
+        assert_eq!(run("filter([1, 2, 3], x -> x > 1)").unwrap(), "[2, 3]\n");
+        assert_eq!(
+            run("print reduce([1, 2, 3], (p, q) -> p * q, 10), reduce([], (p, q) -> p + q, 0)")
+                .unwrap(),
+            "60 0\n"
+        );
+        assert_eq!(
+            run("double(x) = x * 2\nmap([1, 2], double)").unwrap(),
+            "[2, 4]\n"
+        );
+        assert!(matches!(
+            run_err("map([1], (a, b) -> a)"),
+            InterpreterError::InvalidArguments(name) if name == "map"
+        ));
+    }
+
+    #[test]
+    fn nested_destructuring() {
+        assert_eq!(
+            run("[a, [b, c]] = [1, [2, 3]]\nprint a, b, c").unwrap(),
+            "1 2 3\n"
+        );
+        assert!(matches!(
+            run_err("[a, [b, c]] = [1, 2]"),
+            InterpreterError::ExpectedList
+        ));
+        assert!(matches!(
+            run_err("[a, [b, c]] = [1, [2]]"),
+            InterpreterError::DestructuringLength(1, 2)
+        ));
+    }
+
+    #[test]
+    fn underscores_skip_values() {
+        assert_eq!(run("[a, _, c] = [1, 2, 3]\nprint a, c").unwrap(), "1 3\n");
+        assert_eq!(
+            run("[_, b, _] = [1, 2, 3]\nprint b, defined(\"_\")").unwrap(),
+            "2 0\n"
+        );
+    }
+
+    #[test]
+    fn defined_knows_variables_and_functions() {
+        assert_eq!(
+            run("print defined(\"PI\"), defined(\"nope\"), defined(\"sin\")").unwrap(),
+            "1 0 1\n"
+        );
+        assert_eq!(
+            run("a = 1\nf(x) = x\nprint defined(\"a\"), defined(\"f\")").unwrap(),
+            "1 1\n"
+        );
+    }
+
+    #[test]
+    fn equals_compares_nested_lists_whole() {
+        assert_eq!(
+            run("print equals([1, [2]], [1, [3]]), equals([1, [2]], [1, [2]])").unwrap(),
+            "0 1\n"
+        );
+        assert_eq!(
+            run("print equals([1], [1, 2]), equals(1, 1)").unwrap(),
+            "0 1\n"
+        );
+    }
+
+    #[test]
+    fn fill_repeats_any_value() {
+        assert_eq!(run("fill([1, 2], 2)").unwrap(), "[[1, 2], [1, 2]]\n");
+        assert_eq!(run("fill(0, 0)").unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn linspace_includes_both_ends() {
+        assert_eq!(
+            run("linspace(0, 1, 5)").unwrap(),
+            "[0, 0.25, 0.5, 0.75, 1]\n"
+        );
+    }
+
+    /// Runs `src` with strict domain checks and returns the function and argument of the domain
+    /// error it fails with.
+    fn domain_err(src: &str) -> (String, f64) {
+        match run_with(src, |interpreter| interpreter.set_strict_domain(true)) {
+            Err(MathyError::Interpreter(err)) => match err.inner() {
+                InterpreterError::DomainError(name, value) => (name.clone(), *value),
+                err => panic!("expected a domain error, got {:?}", err),
+            },
+            out => panic!("expected a domain error, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn domain_errors_inside_lists() {
+        assert_eq!(run("sqrt([4, 0 - 1])").unwrap(), "[2, nan]\n");
+        assert_eq!(domain_err("sqrt([4, 0 - 1])"), ("sqrt".to_string(), -1.0));
+        assert_eq!(domain_err("ln([[1], [0]])"), ("ln".to_string(), 0.0));
+    }
+
+    #[test]
+    fn strict_domain_checks_each_function() {
+        for (src, name, value) in [
+            ("sqrt(0 - 1)", "sqrt", -1.0),
+            ("ln(0)", "ln", 0.0),
+            ("asin(2)", "asin", 2.0),
+            ("acos(0 - 2)", "acos", -2.0),
+        ] {
+            assert_eq!(domain_err(src), (name.to_string(), value), "{:?}", src);
+        }
+        let printed = run_with(
+            "print sqrt(4), ln(1), asin(1) * 2 == PI, acos(1)",
+            |interpreter| interpreter.set_strict_domain(true),
+        );
+        assert_eq!(printed.unwrap(), "2 0 1 0\n");
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            run("print 2 in [1, 2, 3], 5 in [1, 2, 3], [1] in [[1], 2], 1 in []").unwrap(),
+            "1 0 1 0\n"
+        );
+        assert!(matches!(
+            run_err("1 in 5"),
+            InterpreterError::InvalidOperands(Operator::In)
+        ));
+    }
+
+    #[test]
+    fn defaults_fill_missing_trailing_arguments() {
+        assert_eq!(
+            run("scale(x, by = 10) = x * by\nprint scale(2), scale(2, 3)").unwrap(),
+            "20 6\n"
+        );
+        assert!(matches!(
+            run_err("h(x, y = 1) = x + y\nh(1, 2, 3)"),
+            InterpreterError::InvalidArguments(name) if name == "h"
+        ));
+    }
+
+    #[test]
+    fn rest_parameters_collect_extra_arguments() {
+        assert_eq!(
+            run("f(a, rest...) = len(rest)\nprint f(1), f(1, 2, 3)").unwrap(),
+            "0 2\n"
+        );
+    }
+
+    #[test]
+    fn chained_indexing_checks_each_level() {
+        assert_eq!(run("[[1, 2], [3, 4]][1][0]").unwrap(), "3\n");
+        assert!(matches!(
+            run_err("[[1, 2], [3, 4]][1][2]"),
+            InterpreterError::IndexOutOfRange(index, 2) if index == 2.0
+        ));
+        assert!(matches!(
+            run_err("[[1, 2], [3, 4]][0][0][0]"),
+            InterpreterError::ExpectedList
+        ));
+    }
+
+    #[test]
+    fn ragged_lists_are_not_stretched() {
+        for src in [
+            "[[1, 2], [3]] + [[1, 2], [3, 4]]",
+            "[[1, 2], 3] + [[1, 2], [3, 4]]",
+            "pow([[1, 2], [3]], [[1, 2], [3, 4]])",
+        ] {
+            assert!(
+                matches!(run_err(src), InterpreterError::RaggedList),
+                "{:?}",
+                src
+            );
+        }
+        assert_eq!(
+            run("pow([[1], [2]], [[1, 2], [3, 4]])").unwrap(),
+            "[[1, 1], [8, 16]]\n"
+        );
+        assert_eq!(run("[[1, 2], [3]] + 1").unwrap(), "[[2, 3], [4]]\n");
+    }
+
+    #[test]
+    fn transpose_needs_rows_of_one_length() {
+        assert_eq!(
+            run("print transpose([[1, 2, 3]]), transpose([])").unwrap(),
+            "[[1], [2], [3]] []\n"
+        );
+        assert!(matches!(
+            run_err("transpose([[1, 2], [3]])"),
+            InterpreterError::RaggedList
+        ));
+        assert!(matches!(
+            run_err("transpose([1, 2])"),
+            InterpreterError::InvalidArguments(name) if name == "transpose"
+        ));
+    }
+
+    #[test]
+    fn print_separates_values_with_spaces() {
+        assert_eq!(run("print 1, 2, 3").unwrap(), "1 2 3\n");
+        assert_eq!(run("print 1, [2, 3], \"a\"").unwrap(), "1 [2, 3] a\n");
+    }
+
+    #[test]
+    fn unicode_names() {
+        assert_eq!(
+            run("λ = 2\nx₁ = 3\nprint λ * x₁\nf(α) = α + 1\nf(1)").unwrap(),
+            "6\n2\n"
+        );
+    }
+
+    #[test]
+    fn mod_is_euclidean() {
+        assert_eq!(
+            run("print mod(10, 3), mod([10, 11], 3), mod(-7, 3), mod(7, -3)").unwrap(),
+            "1 [1, 2] 2 1\n"
+        );
+        assert!(matches!(
+            run_err("mod(5, 0)"),
+            InterpreterError::InvalidArguments(name) if name == "mod"
+        ));
+    }
+
+    #[test]
+    fn infinity_and_nan() {
+        assert_eq!(
+            run("print inf > 10 ^ 300, 0 - inf < 0, nan == nan").unwrap(),
+            "1 1 0\n"
+        );
+        assert_eq!(run("print inf, nan, 0 - inf").unwrap(), "inf nan -inf\n");
+    }
+
+    #[test]
+    fn seeding_makes_random_numbers_repeatable() {
+        let src = "s = seed(42)\nprint random(), random()";
+        let first = run(src).unwrap();
+        assert_eq!(first, "0.27399899696215746 0.240879355186972\n");
+        assert_eq!(run(src).unwrap(), first);
+        assert_ne!(run(&src.replace("seed(42)", "seed(43)")).unwrap(), first);
+    }
+
+    #[test]
+    fn seeded_shuffles_and_rolls() {
+        assert_eq!(
+            run("s = seed(7)\nshuffle([1, 2, 3, 4])").unwrap(),
+            "[3, 4, 1, 2]\n"
+        );
+
+        let rolls = run("s = seed(1)\n[randint(1, 6) for _ in fill(0, 200)]").unwrap();
+        let rolls: Vec<&str> = rolls.trim_matches(['[', ']', '\n']).split(", ").collect();
+        assert!(rolls
+            .iter()
+            .all(|roll| ["1", "2", "3", "4", "5", "6"].contains(roll)));
+        assert!(["1", "6"].iter().all(|side| rolls.contains(side)));
+    }
+
+    #[test]
+    fn comparisons_count_as_numbers() {
+        assert_eq!(
+            run("print sum([1 > 0, 2 > 5, 3 > 1]), (1 > 0) + 1, (2 > 1) * [3, 4]").unwrap(),
+            "2 2 [3, 4]\n"
+        );
+    }
+
+    #[test]
+    fn running_sums_and_products() {
+        assert_eq!(
+            run("print cumsum([1, 2, 3]), cumprod([1, 2, 3])").unwrap(),
+            "[1, 3, 6] [1, 2, 6]\n"
+        );
+        assert_eq!(run("print cumsum([]), cumprod([])").unwrap(), "[] []\n");
+        for src in ["cumsum([[1], [2]])", "cumprod(5)"] {
+            assert!(matches!(
+                run_err(src),
+                InterpreterError::InvalidArguments(name) if src.starts_with(name.as_str())
+            ));
+        }
+    }
+
+    #[test]
+    fn evaluate_returns_the_last_expression_instead_of_printing_it() {
+        let mut interpreter = Interpreter::new(crate::parse("1\nx = 2\nx + 3").unwrap());
+        let output = Output::default();
+        interpreter.set_writer(Box::new(output.clone()));
+        assert_eq!(interpreter.evaluate().unwrap(), Some(Data::Float(5.0)));
+        assert_eq!(*output.0.lock().unwrap(), b"1\n");
+
+        let mut interpreter = Interpreter::new(crate::parse("x = 2").unwrap());
+        assert_eq!(interpreter.evaluate().unwrap(), None);
+    }
+
+    #[test]
+    fn semicolons_separate_statements() {
+        assert_eq!(run("x = 1; y = x + 1; print x, y").unwrap(), "1 2\n");
+        assert_eq!(run("for i in [1, 2] { write i; write 0 }").unwrap(), "1020");
+    }
+
+    #[test]
+    fn annotations_check_argument_kinds() {
+        assert_eq!(run("g(v: list) = len(v)\ng([1, 2])").unwrap(), "2\n");
+        let err = run_err("f(v: float) = v\nf([1])");
+        assert!(matches!(
+            &err,
+            InterpreterError::ArgumentType(function, parameter, ParamType::Float, _)
+                if function == "f" && parameter == "v"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Expected argument \"v\" of function \"f\" to be a float, got [1]!"
+        );
+        assert!(matches!(
+            run_err("g(v: list) = len(v)\ng(1)"),
+            InterpreterError::ArgumentType(_, _, ParamType::List, _)
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::interpreter::Data;
+use crate::util::error;
+use std::io::{Error, ErrorKind, Result};
+
+/// A minimal JSON reader producing [`Data`] values.
+///
+/// Only numbers and (nested) arrays are supported as values, plus an object at the top level
+/// mapping variable names to values.
+struct JsonParser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl JsonParser {
+    fn new(content: &str) -> Self {
+        Self {
+            chars: content.chars().collect(),
+            index: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.index += 1;
+                Ok(())
+            }
+            Some(c) => Err(error!(
+                InvalidData,
+                "Expected {:?}, got {:?} at offset {}", expected, c, self.index
+            )),
+            None => Err(error!(
+                InvalidData,
+                "Expected {:?}, got end of input", expected
+            )),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut buf = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(error!(InvalidData, "Unterminated string"));
+            };
+            self.index += 1;
+            match c {
+                '"' => return Ok(buf),
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return Err(error!(InvalidData, "Unterminated string"));
+                    };
+                    self.index += 1;
+                    buf.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        c => c,
+                    });
+                }
+                c => buf.push(c),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Data> {
+        let start = self.index;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.index += 1;
+        }
+
+        let literal: String = self.chars[start..self.index].iter().collect();
+        literal.parse().map(Data::Float).map_err(|_| {
+            error!(
+                InvalidData,
+                "Invalid number {:?} at offset {}", literal, start
+            )
+        })
+    }
+
+    fn parse_array(&mut self) -> Result<Data> {
+        self.expect('[')?;
+        let mut values: Vec<Data> = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.index += 1;
+            return Ok(Data::List(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some(']') => {
+                    self.index += 1;
+                    return Ok(Data::List(values));
+                }
+                _ => {
+                    return Err(error!(
+                        InvalidData,
+                        "Expected ',' or ']' at offset {}", self.index
+                    ))
+                }
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Data> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') => self.parse_array(),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.parse_number(),
+            Some('"') => Err(error!(
+                InvalidData,
+                "Strings are not supported at offset {}", self.index
+            )),
+            Some('{') => Err(error!(
+                InvalidData,
+                "Nested objects are not supported at offset {}", self.index
+            )),
+            Some(c) => Err(error!(
+                InvalidData,
+                "Unsupported value starting with {:?} at offset {}", c, self.index
+            )),
+            None => Err(error!(InvalidData, "Unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Vec<(String, Data)>> {
+        self.expect('{')?;
+        let mut variables: Vec<(String, Data)> = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.index += 1;
+            return Ok(variables);
+        }
+
+        loop {
+            let name = self.parse_string()?;
+            self.expect(':')?;
+            let value = self
+                .parse_value()
+                .map_err(|err| error!(InvalidData, "Invalid value for {:?}: {}", name, err))?;
+            variables.push((name, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some('}') => {
+                    self.index += 1;
+                    return Ok(variables);
+                }
+                _ => {
+                    return Err(error!(
+                        InvalidData,
+                        "Expected ',' or '}}' at offset {}", self.index
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Parses a JSON object into a list of variable names and their values.
+pub fn parse_variables(content: &str) -> Result<Vec<(String, Data)>> {
+    let mut parser = JsonParser::new(content);
+    let variables = parser.parse_object()?;
+
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(error!(
+            InvalidData,
+            "Unexpected trailing data at offset {}", parser.index
+        ));
+    }
+
+    Ok(variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_and_nested_arrays() {
+        let variables = parse_variables(r#" { "x": -1.5e2, "xs": [1, [2, 3], []] } "#).unwrap();
+        assert_eq!(
+            variables,
+            vec![
+                ("x".to_string(), Data::Float(-150.0)),
+                (
+                    "xs".to_string(),
+                    Data::List(vec![
+                        Data::Float(1.0),
+                        Data::List(vec![Data::Float(2.0), Data::Float(3.0)]),
+                        Data::List(vec![]),
+                    ])
+                ),
+            ]
+        );
+        assert_eq!(parse_variables("{}").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn invalid_input() {
+        for content in [
+            "[1, 2]",
+            r#"{"x": 1"#,
+            r#"{"x": "text"}"#,
+            r#"{"x": 1} 2"#,
+            r#"{"x" 1}"#,
+        ] {
+            let err = parse_variables(content).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", content);
+        }
+    }
+}
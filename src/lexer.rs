@@ -1,8 +1,5 @@
-use crate::util::error;
-use std::{
-    fmt::Display,
-    io::{Error, ErrorKind, Result},
-};
+use crate::error::MathyError;
+use std::fmt::Display;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
@@ -10,8 +7,8 @@ pub enum TokenType {
     Ident(String),
     /// Represents a float.
     FloatLiteral(String),
-    /// Represents the '#' character.
-    Comment,
+    /// Represents a string.
+    StringLiteral(String),
     /// Represents an unknown character.
     Unknown(char),
     /// Represents a newline character.
@@ -42,8 +39,40 @@ pub enum TokenType {
     Multi,
     /// Represents the '/' character.
     Div,
+    /// Represents the '//' operator.
+    FloorDiv,
     /// Represents the '^' character.
     Circumflex,
+    /// Represents the '<' character.
+    Less,
+    /// Represents the '<=' operator.
+    LessEquals,
+    /// Represents the '>' character.
+    Greater,
+    /// Represents the '>=' operator.
+    GreaterEquals,
+    /// Represents the '==' operator.
+    DoubleEquals,
+    /// Represents the '!=' operator.
+    NotEquals,
+    /// Represents the '<<' operator.
+    ShiftLeft,
+    /// Represents the '>>' operator.
+    ShiftRight,
+    /// Represents the '&' character.
+    Ampersand,
+    /// Represents the '|' character.
+    Pipe,
+    /// Represents the '?' character.
+    QuestionMark,
+    /// Represents the ':' character.
+    Colon,
+    /// Represents the ';' character, which separates statements like a newline.
+    Semicolon,
+    /// Represents the '->' of a lambda.
+    Arrow,
+    /// Represents the '...' of a rest parameter.
+    Ellipsis,
 }
 
 impl Display for TokenType {
@@ -53,6 +82,7 @@ impl Display for TokenType {
             Self::Minus => "-",
             Self::Multi => "*",
             Self::Div => "/",
+            Self::FloorDiv => "//",
             Self::Comma => ",",
             Self::LeftParen => "(",
             Self::LeftBrace => "{",
@@ -61,12 +91,29 @@ impl Display for TokenType {
             Self::RightBrace => "}",
             Self::RightBracket => "]",
             Self::Equals => "=",
-            Self::Comment => "#",
             Self::Newline => r#"\n"#,
             Self::FloatLiteral(literal) => literal,
+            Self::StringLiteral(literal) => {
+                return write!(f, "{:?}", literal);
+            }
             Self::Ident(name) => name,
             Self::Keyword(keyword) => keyword,
             Self::Circumflex => "^",
+            Self::Less => "<",
+            Self::LessEquals => "<=",
+            Self::Greater => ">",
+            Self::GreaterEquals => ">=",
+            Self::DoubleEquals => "==",
+            Self::NotEquals => "!=",
+            Self::ShiftLeft => "<<",
+            Self::ShiftRight => ">>",
+            Self::Ampersand => "&",
+            Self::Pipe => "|",
+            Self::QuestionMark => "?",
+            Self::Colon => ":",
+            Self::Semicolon => ";",
+            Self::Arrow => "->",
+            Self::Ellipsis => "...",
             Self::Unknown(c) => {
                 return write!(f, "{:?}", c);
             }
@@ -91,14 +138,38 @@ impl From<char> for TokenType {
             '}' => Self::RightBrace,
             ']' => Self::RightBracket,
             '=' => Self::Equals,
-            '#' => Self::Comment,
             '\n' => Self::Newline,
             '^' => Self::Circumflex,
+            '<' => Self::Less,
+            '>' => Self::Greater,
+            '&' => Self::Ampersand,
+            '|' => Self::Pipe,
+            '?' => Self::QuestionMark,
+            ':' => Self::Colon,
+            ';' => Self::Semicolon,
             _ => Self::Unknown(value),
         }
     }
 }
 
+impl TokenType {
+    /// Returns the operator made up of the two characters `first` and `second`, if any.
+    fn from_pair(first: char, second: char) -> Option<Self> {
+        match (first, second) {
+            ('/', '/') => Some(Self::FloorDiv),
+            ('*', '*') => Some(Self::Circumflex),
+            ('<', '<') => Some(Self::ShiftLeft),
+            ('>', '>') => Some(Self::ShiftRight),
+            ('<', '=') => Some(Self::LessEquals),
+            ('>', '=') => Some(Self::GreaterEquals),
+            ('=', '=') => Some(Self::DoubleEquals),
+            ('!', '=') => Some(Self::NotEquals),
+            ('-', '>') => Some(Self::Arrow),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 /// The location of a [`Token`] in the form (file name, column, row).
 pub struct TokenLocation(pub String, pub u32, pub u32);
@@ -120,6 +191,47 @@ impl Display for TokenLocation {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum LexError {
+    UnexpectedEof,
+    MultiplePeriods,
+    UnterminatedString(TokenLocation),
+    UnterminatedBlockComment(TokenLocation),
+    UnexpectedCharacter(char, TokenLocation),
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "Unexpected EOF"),
+            Self::MultiplePeriods => write!(f, "Multiple periods!"),
+            Self::UnterminatedString(loc) => write!(f, "Unterminated string at {}", loc),
+            Self::UnterminatedBlockComment(loc) => {
+                write!(f, "Unterminated block comment at {}", loc)
+            }
+            Self::UnexpectedCharacter(c, loc) => {
+                write!(f, "Unexpected character {:?} at {}", c, loc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+impl LexError {
+    /// Returns where the error occurred, if known.
+    pub fn location(&self) -> Option<&TokenLocation> {
+        match self {
+            Self::UnexpectedEof | Self::MultiplePeriods => None,
+            Self::UnterminatedString(loc)
+            | Self::UnterminatedBlockComment(loc)
+            | Self::UnexpectedCharacter(_, loc) => Some(loc),
+        }
+    }
+}
+
+type LexResult<T> = std::result::Result<T, LexError>;
+
 macro_rules! token {
     ($type:expr, $file:expr, $col:expr, $row:expr) => {
         Token($type, TokenLocation($file, $col, $row))
@@ -128,54 +240,60 @@ macro_rules! token {
 
 pub struct Lexer {
     file_path: String,
-    content: String,
+    /// The source split into characters, so that identifiers can contain non-ASCII letters.
+    content: Vec<char>,
     tokens: Vec<Token>,
     index: usize,
+    /// The location of the first comment in the source, if any.
+    first_comment: Option<TokenLocation>,
 }
 
 impl Lexer {
     pub fn new(file_path: String, content: String) -> Self {
         Self {
             file_path,
-            content,
+            content: content.chars().collect(),
             tokens: Vec::new(),
             index: 0,
+            first_comment: None,
         }
     }
 
+    /// Returns the location of the first comment skipped by [`Lexer::tokenize`].
+    pub fn first_comment(&self) -> Option<&TokenLocation> {
+        self.first_comment.as_ref()
+    }
+
     fn peek(&self, offset: usize) -> Option<char> {
-        if self.index + offset < self.content.len() {
-            self.content.chars().nth(self.index + offset)
-        } else {
-            None
-        }
+        self.content.get(self.index + offset).copied()
     }
 
-    fn consume(&mut self) -> Result<char> {
+    fn consume(&mut self) -> LexResult<char> {
         let cur = self
             .content
-            .chars()
-            .nth(self.index)
-            .ok_or(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF"))?;
+            .get(self.index)
+            .copied()
+            .ok_or(LexError::UnexpectedEof)?;
         self.index += 1;
         Ok(cur)
     }
 
-    fn parse_text(&mut self, col: u32, row: u32) -> Result<u32> {
+    fn parse_text(&mut self, row: u32, col: u32) -> LexResult<u32> {
         let mut col_delta = 0u32;
         let mut buf = String::new();
         buf.push(self.consume()?);
 
         while self
             .peek(0)
-            .is_some_and(|c| c.is_ascii_alphabetic() || c.is_ascii_digit() || c == '_')
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
         {
             col_delta += 1;
             buf.push(self.consume()?);
         }
 
         match buf.as_str() {
-            "from" | "to" | "as" | "with" | "step" | "for" | "in" => self.tokens.push(token!(
+            "from" | "to" | "as" | "with" | "step" | "for" | "in" | "write" | "import" | "xor"
+            | "assert" | "if" | "print" => self.tokens.push(token!(
                 TokenType::Keyword(buf),
                 self.file_path.clone(),
                 col,
@@ -192,7 +310,7 @@ impl Lexer {
         Ok(col_delta)
     }
 
-    fn parse_float(&mut self, row: u32, col: u32) -> Result<u32> {
+    fn parse_float(&mut self, row: u32, col: u32) -> LexResult<u32> {
         let mut col_delta = 0u32;
         let mut buf = String::new();
         let mut period = false;
@@ -207,7 +325,7 @@ impl Lexer {
 
         while self
             .peek(0)
-            .is_some_and(|c| c.is_digit(10) || c == '.' || c == '_')
+            .is_some_and(|c| c.is_ascii_digit() || c == '.' || c == '_')
         {
             if self.peek(0).unwrap() == '_' {
                 self.consume()?;
@@ -217,7 +335,7 @@ impl Lexer {
 
             if self.peek(0).unwrap() == '.' {
                 if period {
-                    return Err(error!(Other, "Multiple periods!"));
+                    return Err(LexError::MultiplePeriods);
                 } else {
                     period = true;
                 }
@@ -240,11 +358,88 @@ impl Lexer {
         Ok(col_delta)
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>> {
+    fn parse_string(&mut self, row: u32, col: u32) -> LexResult<u32> {
+        let start = TokenLocation(self.file_path.clone(), col, row);
+        let mut col_delta = 0u32;
+        let mut buf = String::new();
+        self.consume()?;
+
+        loop {
+            let Some(c) = self.peek(0) else {
+                return Err(LexError::UnterminatedString(start));
+            };
+            col_delta += 1;
+            match c {
+                '"' => {
+                    self.consume()?;
+                    break;
+                }
+                '\n' => return Err(LexError::UnterminatedString(start)),
+                '\\' => {
+                    self.consume()?;
+                    col_delta += 1;
+                    buf.push(match self.consume()? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        c => c,
+                    });
+                }
+                c => {
+                    self.consume()?;
+                    buf.push(c);
+                }
+            }
+        }
+
+        self.tokens.push(token!(
+            TokenType::StringLiteral(buf),
+            self.file_path.clone(),
+            col,
+            row
+        ));
+
+        Ok(col_delta)
+    }
+
+    /// Skips a `#{ ... }#` block comment starting at `row`/`col`, returning the location of its
+    /// last character.
+    fn skip_block_comment(&mut self, row: u32, col: u32) -> LexResult<(u32, u32)> {
+        let start = TokenLocation(self.file_path.clone(), col, row);
+        let mut row = row;
+        let mut col = col + 2;
+        self.consume()?;
+        self.consume()?;
+
+        loop {
+            match self.peek(0) {
+                None => return Err(LexError::UnterminatedBlockComment(start)),
+                Some('}') if self.peek(1) == Some('#') => {
+                    self.consume()?;
+                    self.consume()?;
+                    return Ok((row, col + 1));
+                }
+                Some('\n') => {
+                    row += 1;
+                    col = 1;
+                }
+                Some(_) => col += 1,
+            }
+            self.consume()?;
+        }
+    }
+
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, MathyError> {
         let mut col = 1u32;
         let mut line = 1u32;
         while self.peek(0).is_some() {
             let c = self.peek(0).unwrap();
+            if c == '\r' && self.peek(1) == Some('\n') {
+                // `\r\n` is treated as a single newline.
+                self.consume()?;
+                continue;
+            }
+
             if c == '\n' {
                 self.tokens.push(token!(
                     TokenType::Newline,
@@ -253,22 +448,55 @@ impl Lexer {
                     line
                 ));
                 line += 1;
-                col = 0;
+                col = 1;
                 self.consume()?;
-            } else if c.is_whitespace() {
-                self.consume()?;
-            } else if c.is_ascii_alphabetic() || c == '_' {
-                col += self.parse_text(line, col)?;
-            } else if c == '.' || c.is_digit(10) {
-                col += self.parse_float(line, col)?;
-            } else {
+            } else if c == '#' && self.peek(1) == Some('{') {
+                self.first_comment
+                    .get_or_insert_with(|| TokenLocation(self.file_path.clone(), col, line));
+                (line, col) = self.skip_block_comment(line, col)?;
+            } else if c == '#' {
+                self.first_comment
+                    .get_or_insert_with(|| TokenLocation(self.file_path.clone(), col, line));
+                // Line comments run until the end of the line.
+                while self.peek(0).is_some_and(|c| c != '\n') {
+                    self.consume()?;
+                }
+            } else if c == '.' && self.peek(1) == Some('.') && self.peek(2) == Some('.') {
                 self.tokens.push(token!(
-                    TokenType::from(c),
+                    TokenType::Ellipsis,
                     self.file_path.clone(),
                     col,
                     line
                 ));
                 self.consume()?;
+                self.consume()?;
+                self.consume()?;
+                col += 2;
+            } else if let Some(token_type) =
+                self.peek(1).and_then(|next| TokenType::from_pair(c, next))
+            {
+                self.tokens
+                    .push(token!(token_type, self.file_path.clone(), col, line));
+                self.consume()?;
+                self.consume()?;
+                col += 1;
+            } else if c.is_whitespace() {
+                self.consume()?;
+            } else if c.is_alphabetic() || c == '_' {
+                col += self.parse_text(line, col)?;
+            } else if c == '"' {
+                col += self.parse_string(line, col)?;
+            } else if c == '.' || c.is_ascii_digit() {
+                col += self.parse_float(line, col)?;
+            } else {
+                let token_type = TokenType::from(c);
+                if let TokenType::Unknown(c) = token_type {
+                    let loc = TokenLocation(self.file_path.clone(), col, line);
+                    return Err(LexError::UnexpectedCharacter(c, loc).into());
+                }
+                self.tokens
+                    .push(token!(token_type, self.file_path.clone(), col, line));
+                self.consume()?;
             }
 
             if c != '\n' {
@@ -279,3 +507,81 @@ impl Lexer {
         Ok(self.tokens.to_vec())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(src: &str) -> Vec<TokenType> {
+        crate::tokenize(src)
+            .unwrap()
+            .into_iter()
+            .map(|Token(token_type, _)| token_type)
+            .collect()
+    }
+
+    #[test]
+    fn block_comments_are_skipped() {
+        let tokens = crate::tokenize("1\n#{ a\nb = }#  2").unwrap();
+        let Token(token_type, TokenLocation(_, col, row)) = tokens.last().unwrap();
+        assert_eq!(*token_type, TokenType::FloatLiteral("2.0".to_string()));
+        assert_eq!((*col, *row), (9, 3));
+        assert_eq!(types("#{ x }#"), vec![]);
+        assert!(matches!(
+            crate::tokenize("1\n#{ never closed"),
+            Err(MathyError::Lex(LexError::UnterminatedBlockComment(
+                TokenLocation(_, 1, 2)
+            )))
+        ));
+    }
+
+    #[test]
+    fn crlf_lexes_like_lf() {
+        let positions = |src: &str| -> Vec<(TokenType, u32, u32)> {
+            crate::tokenize(src)
+                .unwrap()
+                .into_iter()
+                .map(|Token(token_type, TokenLocation(_, col, row))| (token_type, col, row))
+                .collect()
+        };
+        let src = "x = 1\ny = [x,\n2]\n";
+        assert_eq!(positions(&src.replace('\n', "\r\n")), positions(src));
+    }
+
+    #[test]
+    fn unknown_characters_are_errors() {
+        assert!(matches!(
+            crate::tokenize("x = @"),
+            Err(MathyError::Lex(LexError::UnexpectedCharacter(
+                '@',
+                TokenLocation(_, 5, 1)
+            )))
+        ));
+    }
+
+    #[test]
+    fn first_comment_is_recorded() {
+        let mut lexer = Lexer::new("a.mth".to_string(), "1\n#{ a\nb }# 2 # c\n3".to_string());
+        lexer.tokenize().unwrap();
+        let Some(TokenLocation(_, col, row)) = lexer.first_comment() else {
+            panic!("expected a comment");
+        };
+        assert_eq!((*col, *row), (1, 2));
+
+        let mut lexer = Lexer::new("a.mth".to_string(), "1 + 2".to_string());
+        lexer.tokenize().unwrap();
+        assert!(lexer.first_comment().is_none());
+    }
+
+    #[test]
+    fn identifiers_may_use_unicode_letters() {
+        assert_eq!(
+            types("λ = x₁"),
+            vec![
+                TokenType::Ident("λ".to_string()),
+                TokenType::Equals,
+                TokenType::Ident("x₁".to_string()),
+            ]
+        );
+    }
+}
@@ -1,4 +1,69 @@
+pub mod error;
 pub mod interpreter;
+pub mod json;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
+pub mod repl;
 pub mod util;
+
+pub use error::MathyError;
+pub use interpreter::{Data, Interpreter};
+pub use lexer::{Token, TokenLocation, TokenType};
+pub use parser::{Expr, Operator, Parsed, Spanned};
+
+use lexer::Lexer;
+use parser::Parser;
+
+/// The file name reported in token locations of sources passed to [`tokenize`] and [`parse`].
+const SOURCE_NAME: &str = "<input>";
+
+/// Splits `src` into tokens.
+///
+/// # Examples
+///
+/// ```
+/// use mathy::TokenType;
+///
+/// let tokens = mathy::tokenize("x = 2").unwrap();
+/// assert_eq!(tokens[0].0, TokenType::Ident("x".to_string()));
+/// assert_eq!(tokens[1].0, TokenType::Equals);
+/// assert!(mathy::tokenize("x = 1 €").is_err());
+/// ```
+pub fn tokenize(src: &str) -> Result<Vec<Token>, MathyError> {
+    Lexer::new(SOURCE_NAME.to_string(), src.to_string()).tokenize()
+}
+
+/// Tokenizes and parses `src` into its statements.
+///
+/// # Examples
+///
+/// ```
+/// use mathy::Parsed;
+///
+/// let parsed = mathy::parse("x = 2\nx * 3").unwrap();
+/// assert!(matches!(parsed[0], Parsed::Declaration(..)));
+/// assert_eq!(parsed[1].to_source(0), "x * 3");
+/// ```
+pub fn parse(src: &str) -> Result<Vec<Parsed>, MathyError> {
+    Parser::new(tokenize(src)?).parse()
+}
+
+/// Runs `src` and returns the value of its last statement if that is a bare expression, as in
+/// `eval("2 + 3")`. Other bare expressions are printed to stdout.
+///
+/// # Examples
+///
+/// ```
+/// use mathy::Data;
+///
+/// assert_eq!(mathy::eval("2 + 3").unwrap(), Some(Data::Float(5.0)));
+/// assert_eq!(mathy::eval("f(x) = x * 2\nf([1, 2])").unwrap(), Some(Data::List(vec![
+///     Data::Float(2.0),
+///     Data::Float(4.0),
+/// ])));
+/// assert_eq!(mathy::eval("x = 1").unwrap(), None);
+/// ```
+pub fn eval(src: &str) -> Result<Option<Data>, MathyError> {
+    Interpreter::new(parse(src)?).evaluate()
+}
@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use crate::{
+    lexer::{Token, TokenLocation, TokenType},
+    parser::{Expr, Parsed},
+};
+
+/// A variable or function that is declared but never referenced.
+#[derive(Debug, Clone)]
+pub struct Unused {
+    pub name: String,
+    pub loc: TokenLocation,
+    pub function: bool,
+}
+
+/// Finds the variables and functions declared in `parsed` that are never referenced. Names are
+/// matched without regard to scope, except that function parameters, loop variables and the
+/// variables of lambdas and comprehensions hide declarations with the same name.
+pub fn unused_names(parsed: &[Parsed]) -> Vec<Unused> {
+    let mut usage = Usage::default();
+    usage.block(parsed, &mut Vec::new());
+
+    usage
+        .declared
+        .into_iter()
+        .filter(|unused| !usage.used.contains(&unused.name))
+        .collect()
+}
+
+#[derive(Default)]
+struct Usage {
+    declared: Vec<Unused>,
+    used: HashSet<String>,
+}
+
+impl Usage {
+    fn declare(&mut self, token: &Token, function: bool) {
+        if let Token(TokenType::Ident(name), loc) = token {
+            self.declared.push(Unused {
+                name: name.clone(),
+                loc: loc.clone(),
+                function,
+            });
+        }
+    }
+
+    fn declare_targets(&mut self, target: &Expr, loc: &TokenLocation) {
+        match target {
+            Expr::Ident(name) if name != "_" => self.declared.push(Unused {
+                name: name.clone(),
+                loc: loc.clone(),
+                function: false,
+            }),
+            Expr::List(targets) => {
+                for target in targets {
+                    self.declare_targets(target, loc);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Walks `block` with the names in `bound` hiding declarations.
+    fn block(&mut self, block: &[Parsed], bound: &mut Vec<String>) {
+        for parsed in block {
+            match parsed {
+                Parsed::Declaration(idents, expr) => {
+                    self.expr(expr, bound);
+                    for ident in idents {
+                        self.declare(ident, false);
+                    }
+                }
+                Parsed::FunctionDecleration(name, parameters, expr) => {
+                    self.declare(name, true);
+                    let len = bound.len();
+                    // Calls to the function from its own body don't count as uses.
+                    if let Token(TokenType::Ident(name), _) = name {
+                        bound.push(name.clone());
+                    }
+                    for parameter in parameters {
+                        if let Some(default) = &parameter.default {
+                            self.expr(default, bound);
+                        }
+                        if let Token(TokenType::Ident(name), _) = &parameter.name {
+                            bound.push(name.clone());
+                        }
+                    }
+                    self.expr(expr, bound);
+                    bound.truncate(len);
+                }
+                Parsed::FromLoop(min, max, ident, step, block) => {
+                    self.expr(min, bound);
+                    self.expr(max, bound);
+                    self.expr(step, bound);
+                    self.loop_block(&[ident], block, bound);
+                }
+                Parsed::ForLoop(ident, list, step, index, block) => {
+                    self.expr(list, bound);
+                    self.expr(step, bound);
+                    let mut idents = vec![&**ident];
+                    idents.extend(index.as_deref());
+                    self.loop_block(&idents, block, bound);
+                }
+                Parsed::Block(block) => self.block(block, bound),
+                Parsed::Destructuring(left, right) => {
+                    self.expr(right, bound);
+                    self.declare_targets(left, &left.span);
+                }
+                Parsed::PrintExpr(expr) | Parsed::Write(expr) => self.expr(expr, bound),
+                Parsed::Print(exprs) => {
+                    for expr in exprs {
+                        self.expr(expr, bound);
+                    }
+                }
+                Parsed::Assert(_, expr, message) => {
+                    self.expr(expr, bound);
+                    if let Some(message) = message {
+                        self.expr(message, bound);
+                    }
+                }
+                Parsed::Import(..) => {}
+            }
+        }
+    }
+
+    fn loop_block(&mut self, idents: &[&Expr], block: &[Parsed], bound: &mut Vec<String>) {
+        let len = bound.len();
+        for ident in idents {
+            if let Expr::Ident(name) = ident {
+                bound.push(name.clone());
+            }
+        }
+        self.block(block, bound);
+        bound.truncate(len);
+    }
+
+    fn expr(&mut self, expr: &Expr, bound: &mut Vec<String>) {
+        match expr {
+            Expr::FloatLiteral(_) | Expr::NegFloatLiteral(_) | Expr::StringLiteral(_) => {}
+            Expr::Ident(name) => {
+                if !bound.contains(name) {
+                    self.used.insert(name.clone());
+                }
+            }
+            Expr::FunctionCall(name, args) => {
+                if !bound.contains(name) {
+                    self.used.insert(name.clone());
+                }
+                for arg in args {
+                    self.expr(arg, bound);
+                }
+            }
+            Expr::Expr(left, _, right) | Expr::Index(left, right) => {
+                self.expr(left, bound);
+                self.expr(right, bound);
+            }
+            Expr::Comparison(first, rest) => {
+                self.expr(first, bound);
+                for (_, expr) in rest {
+                    self.expr(expr, bound);
+                }
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                self.expr(condition, bound);
+                self.expr(then, bound);
+                self.expr(otherwise, bound);
+            }
+            Expr::List(items) => {
+                for item in items {
+                    self.expr(item, bound);
+                }
+            }
+            Expr::Comprehension(body, name, list, filter) => {
+                self.expr(list, bound);
+                bound.push(name.clone());
+                self.expr(body, bound);
+                if let Some(filter) = filter {
+                    self.expr(filter, bound);
+                }
+                bound.pop();
+            }
+            Expr::Lambda(names, body) => {
+                let len = bound.len();
+                bound.extend(names.iter().cloned());
+                self.expr(body, bound);
+                bound.truncate(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unused(src: &str) -> Vec<(String, bool)> {
+        unused_names(&crate::parse(src).unwrap())
+            .into_iter()
+            .map(|unused| (unused.name, unused.function))
+            .collect()
+    }
+
+    #[test]
+    fn reports_unused_declarations() {
+        assert_eq!(
+            unused("x = 1\ny = 2\nf(a) = a\ng(a) = a\ng(y)"),
+            vec![("x".to_string(), false), ("f".to_string(), true)]
+        );
+        assert_eq!(
+            unused("[a, [_, b]] = [1, [2, 3]]\na"),
+            vec![("b".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn parameters_hide_declarations() {
+        // The parameter `x` and the recursive call don't count as uses of the outer names.
+        assert_eq!(
+            unused("x = 1\nf(x) = x > 0 ? f(x - 1) : 0"),
+            vec![("x".to_string(), false), ("f".to_string(), true)]
+        );
+        assert_eq!(
+            unused("x = 1\ny = map([1], x -> x)\nfor x in y {\n    x\n}"),
+            vec![("x".to_string(), false)]
+        );
+        assert!(unused("n = 2\nprint [i * n for i in [1, 2]]").is_empty());
+    }
+}
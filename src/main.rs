@@ -1,45 +1,288 @@
 use std::{
-    env::args,
+    env::{args, var_os},
     fs::read_to_string,
-    io::{Error, ErrorKind, Result},
+    io::{stderr, stdin, Error, ErrorKind, IsTerminal, Read, Result},
     process::exit,
+    thread,
+    time::Instant,
 };
 
-use mathy::{interpreter::Interpreter, lexer::Lexer, parser::Parser, util::error};
+use mathy::{
+    error::MathyError,
+    interpreter::{Interpreter, OutputFormat},
+    json::parse_variables,
+    lexer::{Lexer, Token},
+    lint::unused_names,
+    parser::Parser,
+    repl::{history_path, Repl},
+    util::error,
+};
+
+/// The stack size of the thread running the program. Parsing and evaluation recurse for every
+/// nested expression and function call, so deeply nested input would overflow the default main
+/// thread stack.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The maximum depth of nested user-defined function calls, which [`STACK_SIZE`] leaves plenty of
+/// room for.
+const RECURSION_LIMIT: usize = 1000;
+
+/// The file name used in error locations for programs read from stdin.
+const STDIN_NAME: &str = "<stdin>";
+
+/// The exit code for errors raised while running a program.
+const EXIT_RUNTIME: i32 = 1;
+/// The exit code for lexing and parsing errors.
+const EXIT_SYNTAX: i32 = 2;
+/// The exit code for invalid arguments and unreadable input files.
+const EXIT_USAGE: i32 = 64;
 
-fn main() -> Result<()> {
-    let args_: Vec<String> = args().collect();
+fn main() {
+    let result = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .and_then(|handle| handle.join().unwrap_or_else(|_| exit(101)));
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(EXIT_USAGE);
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
 
-    if args_.len() < 2 {
-        return Err(error!(InvalidInput, "Missing filepath!"));
+/// Prints `err` to stderr in the given format. Plain errors are printed in red with the location
+/// in bold if `color` is set.
+fn report(err: &MathyError, file_path: &str, error_format: OutputFormat, color: bool) {
+    match error_format {
+        OutputFormat::Plain if color => {
+            let mut message = err.to_string();
+            if let Some(loc) = err.location() {
+                let loc = loc.to_string();
+                message = message.replacen(&loc, &format!("{RESET}{BOLD}{loc}{RESET}{RED}"), 1);
+            }
+            eprintln!("{RED}{message}{RESET}");
+        }
+        OutputFormat::Plain => eprintln!("{}", err),
+        OutputFormat::Json => eprintln!("{}", err.to_json(file_path)),
     }
+}
 
-    let file_path = &args_[1];
-    let content = read_to_string(&file_path)?;
+fn run() -> Result<()> {
+    let mut file_path: Option<String> = None;
+    let mut history = true;
+    let mut json_input: Option<String> = None;
+    let mut check = false;
+    let mut dump_ast = false;
+    let mut dump_tokens = false;
+    let mut fmt = false;
+    let mut time = false;
+    let mut warn_unused = false;
+    // Errors are colored when printed to a terminal, unless `NO_COLOR` is set to anything.
+    let mut color = stderr().is_terminal() && var_os("NO_COLOR").is_none_or(|var| var.is_empty());
+    let mut strict_finite = false;
+    let mut strict_domain = false;
+    let mut output_format = OutputFormat::Plain;
+    let mut precision: Option<usize> = None;
+    let mut max_steps: Option<usize> = None;
+    let mut error_format = OutputFormat::Plain;
 
-    let mut lexer = Lexer::new(file_path.to_string(), content);
+    let mut args_ = args().skip(1);
+    while let Some(arg) = args_.next() {
+        match arg.as_str() {
+            "--version" => {
+                println!("mathy {}", env!("CARGO_PKG_VERSION"));
+                return Ok(());
+            }
+            "--no-history" => history = false,
+            "--check" | "-c" => check = true,
+            "--dump-ast" => dump_ast = true,
+            "--dump-tokens" => dump_tokens = true,
+            "--fmt" => fmt = true,
+            "--time" => time = true,
+            "--warn-unused" => warn_unused = true,
+            "--no-color" => color = false,
+            "--strict-finite" => strict_finite = true,
+            "--strict-domain" => strict_domain = true,
+            "--output" => match args_.next().as_deref() {
+                Some("plain") => output_format = OutputFormat::Plain,
+                Some("json") => output_format = OutputFormat::Json,
+                _ => {
+                    return Err(error!(
+                        InvalidInput,
+                        "Expected plain or json after --output!"
+                    ))
+                }
+            },
+            "--error-format" => match args_.next().as_deref() {
+                Some("plain") => error_format = OutputFormat::Plain,
+                Some("json") => error_format = OutputFormat::Json,
+                _ => {
+                    return Err(error!(
+                        InvalidInput,
+                        "Expected plain or json after --error-format!"
+                    ))
+                }
+            },
+            "--precision" => match args_.next().and_then(|arg| arg.parse().ok()) {
+                Some(value) => precision = Some(value),
+                None => {
+                    return Err(error!(
+                        InvalidInput,
+                        "Expected a whole number after --precision!"
+                    ))
+                }
+            },
+            "--max-steps" => match args_.next().and_then(|arg| arg.parse().ok()) {
+                Some(value) => max_steps = Some(value),
+                None => {
+                    return Err(error!(
+                        InvalidInput,
+                        "Expected a whole number after --max-steps!"
+                    ))
+                }
+            },
+            "--json-input" => {
+                let Some(path) = args_.next() else {
+                    return Err(error!(InvalidInput, "Missing path after --json-input!"));
+                };
+                json_input = Some(path);
+            }
+            _ => file_path = Some(arg),
+        }
+    }
+
+    let Some(file_path) = file_path else {
+        let history_path = if history { history_path() } else { None };
+        let mut repl = Repl::new(history_path);
+        repl.set_recursion_limit(RECURSION_LIMIT);
+        if let Err(err) = repl.run() {
+            eprintln!("{}", err);
+            exit(EXIT_RUNTIME);
+        }
+        return Ok(());
+    };
+
+    // A path of `-` reads the program from stdin.
+    let (file_path, content) = if file_path == "-" {
+        let mut content = String::new();
+        stdin()
+            .read_to_string(&mut content)
+            .map_err(|err| error!(InvalidInput, "{}: {}", STDIN_NAME, err))?;
+        (STDIN_NAME.to_string(), content)
+    } else {
+        let content = read_to_string(&file_path)
+            .map_err(|err| error!(InvalidInput, "{}: {}", file_path, err))?;
+        (file_path, content)
+    };
+
+    let start = Instant::now();
+    let mut lexer = Lexer::new(file_path.to_string(), content.clone());
     let out = lexer.tokenize();
     if let Err(err) = out {
-        if let Some(msg) = err.into_inner() {
-            eprintln!("{}", msg);
-        }
-        exit(1);
+        report(&err, &file_path, error_format, color);
+        exit(EXIT_SYNTAX);
+    }
+    if time {
+        eprintln!("lexing: {:?}", start.elapsed());
     }
 
     let out = out.unwrap();
+    if dump_tokens {
+        for Token(token_type, loc) in &out {
+            eprintln!("{}: {:?}", loc, token_type);
+        }
+    }
+
+    let start = Instant::now();
     let mut parser = Parser::new(out);
     let out = parser.parse();
     if let Err(err) = out {
-        eprintln!("{}", Error::from(err));
-        exit(1);
+        report(&err, &file_path, error_format, color);
+        exit(EXIT_SYNTAX);
+    }
+    if time {
+        eprintln!("parsing: {:?}", start.elapsed());
     }
 
-    let mut interpreter = Interpreter::new(out.unwrap());
-    if let Err(err) = interpreter.interpret() {
-        if let Some(msg) = err.into_inner() {
-            eprintln!("{}", msg);
+    let parsed = out.unwrap();
+    if dump_ast {
+        eprintln!("{:#?}", parsed);
+        return Ok(());
+    }
+
+    if warn_unused {
+        for unused in unused_names(&parsed) {
+            let kind = if unused.function {
+                "function"
+            } else {
+                "variable"
+            };
+            eprintln!("{}: warning: unused {} {:?}", unused.loc, kind, unused.name);
+        }
+    }
+
+    if fmt {
+        // The formatter prints the program from its AST, which has no comments.
+        if let Some(loc) = lexer.first_comment() {
+            return Err(error!(
+                InvalidInput,
+                "{}: --fmt can't keep comments, remove them to format the file", loc
+            ));
+        }
+        let source_lines: Vec<&str> = content.lines().collect();
+        for (i, (parsed, &line)) in parsed.iter().zip(parser.statement_lines()).enumerate() {
+            // Keep one blank line where the source had blank lines between statements.
+            let blank_before = line >= 2
+                && source_lines
+                    .get(line as usize - 2)
+                    .is_some_and(|source| source.trim().is_empty());
+            if i > 0 && blank_before {
+                println!();
+            }
+            println!("{}", parsed.to_source(0));
         }
-        exit(1);
+        return Ok(());
+    }
+
+    if check {
+        println!("{}: ok", file_path);
+        return Ok(());
+    }
+
+    let mut interpreter = Interpreter::new(parsed);
+    interpreter.set_output_format(output_format);
+    interpreter.set_precision(precision);
+    interpreter.set_strict_finite(strict_finite);
+    interpreter.set_strict_domain(strict_domain);
+    interpreter.set_step_limit(max_steps);
+    interpreter.set_recursion_limit(RECURSION_LIMIT);
+    if file_path != STDIN_NAME {
+        interpreter
+            .set_file_path(&file_path)
+            .map_err(|err| error!(InvalidInput, "{}: {}", file_path, err))?;
+    }
+    if let Some(json_path) = json_input {
+        match read_to_string(&json_path).and_then(|content| parse_variables(&content)) {
+            Ok(variables) => {
+                for (name, data) in variables {
+                    interpreter.set_variable(name, data);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: {}", json_path, err);
+                exit(EXIT_USAGE);
+            }
+        }
+    }
+    let start = Instant::now();
+    if let Err(err) = interpreter.interpret() {
+        report(&err, &file_path, error_format, color);
+        exit(EXIT_RUNTIME);
+    }
+    if time {
+        eprintln!("interpreting: {:?}", start.elapsed());
     }
 
     Ok(())
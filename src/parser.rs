@@ -1,7 +1,13 @@
-use std::io::{Error, ErrorKind};
+use std::fmt::Display;
+use std::ops::Deref;
+use std::path::Path;
 
+use crate::error::MathyError;
 use crate::lexer::{Token, TokenLocation, TokenType};
-use crate::util::error;
+
+/// Keywords that start a statement. They are reserved, while any other keyword is used as a name
+/// where a keyword isn't expected.
+const STATEMENT_KEYWORDS: &[&str] = &["from", "for", "write", "print", "import", "assert"];
 
 #[derive(Debug, Clone)]
 pub enum Operator {
@@ -9,17 +15,166 @@ pub enum Operator {
     Minus,
     Multi,
     Div,
+    FloorDiv,
     Pow,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    BitAnd,
+    BitOr,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+    /// Membership in a list, `x in list`.
+    In,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Multi => "*",
+            Self::Div => "/",
+            Self::FloorDiv => "//",
+            Self::Pow => "^",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::Xor => "xor",
+            Self::ShiftLeft => "<<",
+            Self::ShiftRight => ">>",
+            Self::In => "in",
+        };
+
+        write!(f, "{}", c)
+    }
+}
+
+impl Operator {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Less
+                | Self::LessEqual
+                | Self::Greater
+                | Self::GreaterEqual
+                | Self::Equal
+                | Self::NotEqual
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     FloatLiteral(String),
     NegFloatLiteral(String),
+    StringLiteral(String),
     Ident(String),
     FunctionCall(String, Vec<Expr>),
     Expr(Box<Expr>, Operator, Box<Expr>),
+    /// A chain of comparisons such as `a < b <= c`, true if every comparison holds.
+    Comparison(Box<Expr>, Vec<(Operator, Expr)>),
+    /// `condition ? then : otherwise`
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
     List(Vec<Expr>),
+    /// `[body for name in list if filter]`
+    Comprehension(Box<Expr>, String, Box<Expr>, Option<Box<Expr>>),
+    /// `list[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `x -> body` or `(x, y) -> body`, passed to functions like `map`.
+    Lambda(Vec<String>, Box<Expr>),
+}
+
+/// How tightly `expr` binds when formatted, with literals and calls binding tightest.
+fn expr_prec(expr: &Expr) -> usize {
+    match expr {
+        Expr::Expr(_, op, _) => get_prec(op),
+        Expr::Comparison(..) => 1,
+        Expr::Conditional(..) | Expr::Lambda(..) => 0,
+        _ => usize::MAX,
+    }
+}
+
+/// Formats `expr`, in parentheses if it binds looser than `min_prec`.
+fn operand_source(expr: &Expr, min_prec: usize) -> String {
+    if expr_prec(expr) < min_prec {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+fn join<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Display for Expr {
+    /// Formats the expression as mathy source.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FloatLiteral(value) => write!(f, "{}", value.strip_suffix(".0").unwrap_or(value)),
+            Self::NegFloatLiteral(value) => {
+                write!(f, "-{}", value.strip_suffix(".0").unwrap_or(value))
+            }
+            Self::StringLiteral(value) => write!(f, "{:?}", value),
+            Self::Ident(name) => write!(f, "{}", name),
+            Self::FunctionCall(name, args) => write!(f, "{}({})", name, join(args)),
+            Self::Expr(left, op, right) => {
+                // Operators are left-associative, so an operand on the right with the same
+                // precedence needs parentheses.
+                let prec = get_prec(op);
+                write!(
+                    f,
+                    "{} {} {}",
+                    operand_source(left, prec),
+                    op,
+                    operand_source(right, prec + 1)
+                )
+            }
+            Self::Comparison(first, chain) => {
+                write!(f, "{}", operand_source(first, 2))?;
+                for (op, expr) in chain {
+                    write!(f, " {} {}", op, operand_source(expr, 2))?;
+                }
+                Ok(())
+            }
+            Self::Conditional(condition, then, otherwise) => write!(
+                f,
+                "{} ? {} : {}",
+                operand_source(condition, 1),
+                then,
+                otherwise
+            ),
+            Self::List(exprs) => write!(f, "[{}]", join(exprs)),
+            Self::Comprehension(body, name, list, filter) => {
+                write!(f, "[{} for {} in {}", body, name, list)?;
+                if let Some(filter) = filter {
+                    write!(f, " if {}", filter)?;
+                }
+                write!(f, "]")
+            }
+            Self::Index(list, index) => {
+                write!(f, "{}[{}]", operand_source(list, usize::MAX), index)
+            }
+            Self::Lambda(parameters, body) if parameters.len() == 1 => {
+                write!(f, "{} -> {}", parameters[0], body)
+            }
+            Self::Lambda(parameters, body) => write!(f, "({}) -> {}", join(parameters), body),
+        }
+    }
 }
 
 impl From<f64> for Expr {
@@ -34,18 +189,213 @@ impl From<&f64> for Expr {
     }
 }
 
+/// A node along with the location of the first token it was parsed from. Only the expressions
+/// held directly by a [`Parsed`] statement are spanned; the nodes nested inside them are not.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: TokenLocation,
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T: Display> Display for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+/// The kind of argument a parameter is annotated with, as in `f(x: float, xs: list)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    Float,
+    List,
+}
+
+impl Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Float => write!(f, "float"),
+            Self::List => write!(f, "list"),
+        }
+    }
+}
+
+/// A parameter of a function declaration.
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: Token,
+    /// The kind of argument the parameter accepts, checked when the function is called. For a
+    /// rest parameter, every collected argument is checked.
+    pub annotation: Option<ParamType>,
+    /// The value used when the argument is left out, as in `f(x, y = 1)`.
+    pub default: Option<Expr>,
+    /// Whether this is a trailing rest parameter, `nums...`, collecting the remaining
+    /// arguments into a list.
+    pub rest: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum Parsed {
-    FunctionDecleration(Token, Vec<Token>, Expr),
-    FromLoop(Expr, Expr, Expr, Expr, Vec<Parsed>),
-    ForLoop(Expr, Expr, Vec<Parsed>),
+    FunctionDecleration(Token, Vec<Parameter>, Spanned<Expr>),
+    FromLoop(
+        Spanned<Expr>,
+        Spanned<Expr>,
+        Spanned<Expr>,
+        Spanned<Expr>,
+        Vec<Parsed>,
+    ),
+    ForLoop(
+        Spanned<Expr>,
+        Spanned<Expr>,
+        Spanned<Expr>,
+        Option<Spanned<Expr>>,
+        Vec<Parsed>,
+    ),
     Block(Vec<Parsed>),
-    Declaration(Token, Expr),
-    Destructuring(Expr, Expr),
-    PrintExpr(Expr),
+    Declaration(Vec<Token>, Spanned<Expr>),
+    Destructuring(Spanned<Expr>, Spanned<Expr>),
+    PrintExpr(Spanned<Expr>),
+    /// Prints an expression without a trailing newline.
+    Write(Spanned<Expr>),
+    /// Prints several expressions on one line, separated by spaces.
+    Print(Vec<Spanned<Expr>>),
+    /// Runs another file, keeping its declarations. Holds the `import` keyword, the path as
+    /// written and the path of the file, which is relative to the importing file.
+    Import(Token, String, String),
+    /// Fails unless the expression is true, with an optional message.
+    Assert(Token, Spanned<Expr>, Option<Spanned<Expr>>),
+}
+
+/// One level of indentation in formatted source.
+const INDENT: &str = "    ";
+
+/// Formats `block` as mathy source, with its statements one level deeper than `depth`.
+fn block_source(block: &[Parsed], depth: usize) -> String {
+    let mut out = String::from("{\n");
+    for parsed in block {
+        out.push_str(&parsed.to_source(depth + 1));
+        out.push('\n');
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+    out
+}
+
+/// The name of an identifier token.
+fn ident_source(Token(token_type, _): &Token) -> String {
+    match token_type {
+        TokenType::Ident(name) => name.to_string(),
+        token_type => token_type.to_string(),
+    }
+}
+
+/// Formats the `with step` clause of a loop, which is left out for the default step of 1.
+fn step_source(step: &Expr) -> String {
+    match step {
+        Expr::FloatLiteral(value) if value == "1.0" => String::new(),
+        step => format!(" with step {}", step),
+    }
+}
+
+impl Parsed {
+    /// Returns where the statement starts, if known. Blocks have no location.
+    pub fn span(&self) -> Option<&TokenLocation> {
+        match self {
+            Self::FunctionDecleration(Token(_, loc), ..)
+            | Self::Assert(Token(_, loc), ..)
+            | Self::Import(Token(_, loc), ..) => Some(loc),
+            Self::Declaration(idents, _) => idents.first().map(|Token(_, loc)| loc),
+            Self::FromLoop(expr, ..)
+            | Self::ForLoop(expr, ..)
+            | Self::Destructuring(expr, _)
+            | Self::PrintExpr(expr)
+            | Self::Write(expr) => Some(&expr.span),
+            Self::Print(exprs) => exprs.first().map(|expr| &expr.span),
+            Self::Block(_) => None,
+        }
+    }
+
+    /// Formats the statement as mathy source, indented by `depth` levels. Comments aren't kept.
+    pub fn to_source(&self, depth: usize) -> String {
+        let source = match self {
+            Self::FunctionDecleration(name, parameters, expr) => {
+                let parameters: Vec<String> = parameters
+                    .iter()
+                    .map(
+                        |Parameter {
+                             name,
+                             annotation,
+                             default,
+                             rest,
+                         }| {
+                            let name = match annotation {
+                                Some(annotation) => {
+                                    format!("{}: {}", ident_source(name), annotation)
+                                }
+                                None => ident_source(name),
+                            };
+                            match default {
+                                Some(default) => format!("{} = {}", name, default),
+                                None if *rest => format!("{}...", name),
+                                None => name,
+                            }
+                        },
+                    )
+                    .collect();
+                format!("{}({}) = {}", ident_source(name), join(&parameters), expr)
+            }
+            Self::FromLoop(min, max, ident, step, block) => format!(
+                "from {} to {} as {}{} {}",
+                min,
+                max,
+                ident,
+                step_source(step),
+                block_source(block, depth)
+            ),
+            Self::ForLoop(ident, list, step, index, block) => {
+                let index = match index {
+                    Some(index) => format!(" with index {}", index),
+                    None => String::new(),
+                };
+                format!(
+                    "for {} in {}{}{} {}",
+                    ident,
+                    list,
+                    step_source(step),
+                    index,
+                    block_source(block, depth)
+                )
+            }
+            Self::Block(block) => block_source(block, depth),
+            Self::Declaration(idents, expr) => {
+                let mut out = String::new();
+                for ident in idents {
+                    out.push_str(&format!("{} = ", ident_source(ident)));
+                }
+                out.push_str(&expr.to_string());
+                out
+            }
+            Self::Destructuring(left, right) => format!("{} = {}", left, right),
+            Self::PrintExpr(expr) => expr.to_string(),
+            Self::Write(expr) => format!("write {}", expr),
+            Self::Print(exprs) => format!("print {}", join(exprs)),
+            Self::Import(_, path, _) => format!("import {:?}", path),
+            Self::Assert(_, expr, None) => format!("assert {}", expr),
+            Self::Assert(_, expr, Some(message)) => format!("assert {}, {}", expr, message),
+        };
+
+        format!("{}{}", INDENT.repeat(depth), source)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParseError {
     EOF,
     MissingLiteral(TokenLocation),
@@ -56,43 +406,93 @@ pub enum ParseError {
     ExpectedGotToken(String, TokenType, TokenLocation),
 }
 
-impl From<ParseError> for Error {
-    fn from(value: ParseError) -> Error {
-        match value {
-            ParseError::EOF => error!(UnexpectedEof, "End of tokens!"),
-            ParseError::MissingLiteral(loc) => error!(Other, "Missing literal at {}", loc),
-            ParseError::UnexpectedToken(t, loc) => {
-                error!(Other, "Unexpected token {} at {}", t, loc)
-            }
-            ParseError::UnexpectedKeyword(keyword, loc) => {
-                error!(Other, "Unexpected keyword {:?} at {}", keyword, loc)
-            }
-            ParseError::Expected(expected, loc) => {
-                error!(Other, "Expected {:?} at {}", expected, loc)
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EOF => write!(f, "End of tokens!"),
+            Self::MissingLiteral(loc) => write!(f, "Missing literal at {}", loc),
+            Self::UnexpectedToken(t, loc) => write!(f, "Unexpected token {} at {}", t, loc),
+            Self::UnexpectedKeyword(keyword, loc) => {
+                write!(f, "Unexpected keyword {:?} at {}", keyword, loc)
             }
-            ParseError::ExpectedGot(expected, got, loc) => {
-                error!(Other, "Expected {:?}, got {:?} at {}", expected, got, loc)
+            Self::Expected(expected, loc) => write!(f, "Expected {:?} at {}", expected, loc),
+            Self::ExpectedGot(expected, got, loc) => {
+                write!(f, "Expected {:?}, got {:?} at {}", expected, got, loc)
             }
-            ParseError::ExpectedGotToken(expected, got, loc) => {
-                error!(Other, "Expected {:?}, got {} at {}", expected, got, loc)
+            Self::ExpectedGotToken(expected, got, loc) => {
+                write!(f, "Expected {:?}, got {} at {}", expected, got, loc)
             }
         }
     }
 }
 
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Returns where the error occurred, if known.
+    pub fn location(&self) -> Option<&TokenLocation> {
+        match self {
+            Self::EOF => None,
+            Self::MissingLiteral(loc)
+            | Self::UnexpectedToken(_, loc)
+            | Self::UnexpectedKeyword(_, loc)
+            | Self::Expected(_, loc)
+            | Self::ExpectedGot(_, _, loc)
+            | Self::ExpectedGotToken(_, _, loc) => Some(loc),
+        }
+    }
+}
+
 type ParseResult<T> = std::result::Result<T, ParseError>;
 
 pub struct Parser {
     tokens: Vec<Token>,
     parsed: Vec<Parsed>,
+    /// The line each top-level statement starts on.
+    lines: Vec<u32>,
     index: usize,
 }
 
 fn get_prec(op: &Operator) -> usize {
     match op {
-        Operator::Plus | Operator::Minus => 1,
-        Operator::Multi | Operator::Div => 2,
-        Operator::Pow => 3,
+        Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+        | Operator::Equal
+        | Operator::NotEqual
+        | Operator::In => 1,
+        Operator::BitOr => 2,
+        Operator::Xor => 3,
+        Operator::BitAnd => 4,
+        Operator::ShiftLeft | Operator::ShiftRight => 5,
+        Operator::Plus | Operator::Minus => 6,
+        Operator::Multi | Operator::Div | Operator::FloorDiv => 7,
+        Operator::Pow => 8,
+    }
+}
+
+fn get_operator(token_type: &TokenType) -> Option<Operator> {
+    match token_type {
+        TokenType::Plus => Some(Operator::Plus),
+        TokenType::Minus => Some(Operator::Minus),
+        TokenType::Multi => Some(Operator::Multi),
+        TokenType::Div => Some(Operator::Div),
+        TokenType::FloorDiv => Some(Operator::FloorDiv),
+        TokenType::Circumflex => Some(Operator::Pow),
+        TokenType::Less => Some(Operator::Less),
+        TokenType::LessEquals => Some(Operator::LessEqual),
+        TokenType::Greater => Some(Operator::Greater),
+        TokenType::GreaterEquals => Some(Operator::GreaterEqual),
+        TokenType::DoubleEquals => Some(Operator::Equal),
+        TokenType::NotEquals => Some(Operator::NotEqual),
+        TokenType::ShiftLeft => Some(Operator::ShiftLeft),
+        TokenType::ShiftRight => Some(Operator::ShiftRight),
+        TokenType::Ampersand => Some(Operator::BitAnd),
+        TokenType::Pipe => Some(Operator::BitOr),
+        TokenType::Keyword(keyword) if keyword == "xor" => Some(Operator::Xor),
+        TokenType::Keyword(keyword) if keyword == "in" => Some(Operator::In),
+        _ => None,
     }
 }
 
@@ -101,6 +501,7 @@ impl Parser {
         Self {
             tokens,
             parsed: Vec::new(),
+            lines: Vec::new(),
             index: 0,
         }
     }
@@ -123,32 +524,138 @@ impl Parser {
         }
     }
 
-    fn parse_expr(&mut self, min_prec: usize, is_function: bool) -> ParseResult<Expr> {
+    fn skip_newlines(&mut self) {
+        while self
+            .peek(0)
+            .is_some_and(|Token(t, _)| t == &TokenType::Newline)
+        {
+            self.index += 1;
+        }
+    }
+
+    /// Turns the keyword at `offset`, if any, into an identifier of the same name, for keywords
+    /// used where a name is expected. Keywords starting statements are reserved and left as they
+    /// are.
+    fn keyword_as_ident(&mut self, offset: usize) {
+        if let Some(Token(token_type, _)) = self.tokens.get_mut(self.index + offset) {
+            if let TokenType::Keyword(keyword) = token_type {
+                if !STATEMENT_KEYWORDS.contains(&keyword.as_str()) {
+                    let name = std::mem::take(keyword);
+                    *token_type = TokenType::Ident(name);
+                }
+            }
+        }
+    }
+
+    /// Returns the parameters if the next tokens are the parameter list `(x, y) ->` of a lambda.
+    fn lambda_parameters(&self) -> Option<Vec<String>> {
+        let mut parameters = Vec::new();
+        let mut offset = 1;
+        while let Some(Token(TokenType::Ident(name), _)) = self.peek(offset) {
+            parameters.push(name.to_string());
+            offset += 1;
+            match self.peek(offset) {
+                Some(Token(TokenType::Comma, _)) => offset += 1,
+                _ => break,
+            }
+        }
+        match (self.peek(offset), self.peek(offset + 1)) {
+            (Some(Token(TokenType::RightParen, _)), Some(Token(TokenType::Arrow, _))) => {
+                Some(parameters)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the next token is the keyword `keyword`.
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek(0)
+            .is_some_and(|Token(t, _)| matches!(t, TokenType::Keyword(k) if k == keyword))
+    }
+
+    /// Fails with the location `open` of an opening delimiter if the line ends before the
+    /// `closing` delimiter is reached.
+    fn check_terminated(&self, closing: &str, open: &TokenLocation) -> ParseResult<()> {
+        match self.peek(0) {
+            None | Some(Token(TokenType::Newline | TokenType::Semicolon, _)) => {
+                Err(ParseError::Expected(closing.to_string(), open.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the next token is `closing`, spelled `name`, which closes the delimiter opened
+    /// at `open`.
+    fn check_closed(
+        &self,
+        closing: TokenType,
+        name: &str,
+        open: &TokenLocation,
+    ) -> ParseResult<()> {
+        self.check_terminated(name, open)?;
+        match self.peek(0) {
+            Some(Token(t, loc)) if *t != closing => Err(ParseError::ExpectedGotToken(
+                name.to_string(),
+                t.clone(),
+                loc.clone(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses an expression along with its location.
+    fn parse_spanned_expr(&mut self) -> ParseResult<Spanned<Expr>> {
+        let Some(Token(_, span)) = self.peek(0) else {
+            return Err(ParseError::EOF);
+        };
+        let span = span.clone();
+        let node = self.parse_expr(1)?;
+        Ok(Spanned { node, span })
+    }
+
+    fn parse_expr(&mut self, min_prec: usize) -> ParseResult<Expr> {
         let mut left: Expr;
+        self.keyword_as_ident(0);
         if self.peek(0).is_some() {
             let token = self.peek(0).unwrap().clone();
             let token_type = &token.0;
             let loc = &token.1;
             if let TokenType::FloatLiteral(val) = token_type {
                 left = Expr::FloatLiteral(val.to_string());
+            } else if let TokenType::StringLiteral(val) = token_type {
+                left = Expr::StringLiteral(val.to_string());
             } else if let TokenType::Ident(name) = token_type {
                 if self
+                    .peek(1)
+                    .is_some_and(|Token(t, _)| t == &TokenType::Arrow)
+                {
+                    self.consume()?;
+                    self.consume()?;
+                    let body = self.parse_expr(1)?;
+                    return Ok(Expr::Lambda(vec![name.to_string()], Box::new(body)));
+                } else if self
                     .peek(1)
                     .is_some_and(|Token(t, _)| t == &TokenType::LeftParen)
                 {
+                    let open = self.peek(1).unwrap().1.clone();
                     self.consume()?;
                     self.consume()?;
 
                     let mut args: Vec<Expr> = Vec::new();
 
-                    while self
+                    while !self
                         .peek(0)
-                        .is_some_and(|Token(t, _)| t != &TokenType::RightParen)
+                        .is_some_and(|Token(t, _)| t == &TokenType::RightParen)
                     {
-                        if self.peek(0).unwrap().0 == TokenType::Comma {
+                        self.check_terminated(")", &open)?;
+                        args.push(self.parse_expr(1)?);
+                        // Elements are separated by commas, a trailing one is allowed.
+                        if self
+                            .peek(0)
+                            .is_some_and(|Token(t, _)| t == &TokenType::Comma)
+                        {
                             self.consume()?;
                         }
-                        args.push(self.parse_expr(1, is_function)?);
                     }
                     left = Expr::FunctionCall(name.to_string(), args);
                 } else {
@@ -161,23 +668,41 @@ impl Parser {
                 left = Expr::NegFloatLiteral(val.to_string());
                 self.consume()?;
             } else if let TokenType::LeftParen = token_type {
+                if let Some(parameters) = self.lambda_parameters() {
+                    while self.consume()?.0 != TokenType::Arrow {}
+                    let body = self.parse_expr(1)?;
+                    return Ok(Expr::Lambda(parameters, Box::new(body)));
+                }
                 self.consume()?;
-                left = self.parse_expr(1, is_function)?;
+                left = self.parse_expr(1)?;
+                self.check_closed(TokenType::RightParen, ")", loc)?;
             } else if let TokenType::LeftBracket = token_type {
                 self.consume()?;
                 let mut out: Vec<Expr> = Vec::new();
+                let mut comprehension: Option<Expr> = None;
 
-                while self
+                while !self
                     .peek(0)
-                    .is_some_and(|Token(t, _)| t != &TokenType::RightBracket)
+                    .is_some_and(|Token(t, _)| t == &TokenType::RightBracket)
                 {
-                    if self.peek(0).unwrap().0 == TokenType::Comma {
+                    self.check_terminated("]", loc)?;
+                    let expr = self.parse_expr(1)?;
+                    if out.is_empty() && self.peek_keyword("for") {
+                        comprehension = Some(self.parse_comprehension(expr)?);
+                        self.check_closed(TokenType::RightBracket, "]", loc)?;
+                        break;
+                    }
+                    out.push(expr);
+                    // Elements are separated by commas, a trailing one is allowed.
+                    if self
+                        .peek(0)
+                        .is_some_and(|Token(t, _)| t == &TokenType::Comma)
+                    {
                         self.consume()?;
                     }
-                    out.push(self.parse_expr(1, is_function)?);
                 }
 
-                left = Expr::List(out);
+                left = comprehension.unwrap_or(Expr::List(out));
             } else {
                 return Err(ParseError::UnexpectedToken(token_type.clone(), loc.clone()));
             }
@@ -186,6 +711,27 @@ impl Parser {
             return Err(ParseError::EOF);
         }
 
+        // Indexing, `list[i]`, which can be chained for nested lists.
+        while self
+            .peek(0)
+            .is_some_and(|Token(t, _)| t == &TokenType::LeftBracket)
+        {
+            let open = self.consume()?.1.clone();
+            let index = self.parse_expr(1)?;
+            self.check_terminated("]", &open)?;
+            match self.consume()? {
+                Token(TokenType::RightBracket, _) => {}
+                Token(t, loc) => {
+                    return Err(ParseError::ExpectedGotToken(
+                        "]".to_string(),
+                        t.clone(),
+                        loc.clone(),
+                    ))
+                }
+            }
+            left = Expr::Index(Box::new(left), Box::new(index));
+        }
+
         loop {
             let cur = self.peek(0);
 
@@ -193,13 +739,8 @@ impl Parser {
                 break;
             }
 
-            let op = match &cur.unwrap().0 {
-                TokenType::Plus => Operator::Plus,
-                TokenType::Minus => Operator::Minus,
-                TokenType::Multi => Operator::Multi,
-                TokenType::Div => Operator::Div,
-                TokenType::Circumflex => Operator::Pow,
-                _ => return Ok(left),
+            let Some(op) = get_operator(&cur.unwrap().0) else {
+                break;
             };
 
             let prec = get_prec(&op);
@@ -209,9 +750,49 @@ impl Parser {
             }
 
             self.consume()?;
-            let right = self.parse_expr(prec + 1, is_function)?;
+            // An expression continues on the next line after a binary operator.
+            self.skip_newlines();
+            let right = self.parse_expr(prec + 1)?;
 
-            left = Expr::Expr(Box::new(left.clone()), op, Box::new(right));
+            if op.is_comparison() {
+                // `a < b < c` means `a < b and b < c` rather than `(a < b) < c`.
+                let mut chain = vec![(op, right)];
+                while let Some(op) = self
+                    .peek(0)
+                    .and_then(|Token(t, _)| get_operator(t))
+                    .filter(Operator::is_comparison)
+                {
+                    self.consume()?;
+                    self.skip_newlines();
+                    chain.push((op, self.parse_expr(prec + 1)?));
+                }
+                left = Expr::Comparison(Box::new(left), chain);
+            } else {
+                left = Expr::Expr(Box::new(left.clone()), op, Box::new(right));
+            }
+        }
+
+        // The conditional binds looser than any operator, so it's only parsed at the outermost
+        // level of an expression.
+        if min_prec <= 1
+            && self
+                .peek(0)
+                .is_some_and(|Token(t, _)| t == &TokenType::QuestionMark)
+        {
+            self.consume()?;
+            let then = self.parse_expr(1)?;
+            match self.consume()? {
+                Token(TokenType::Colon, _) => {}
+                Token(t, loc) => {
+                    return Err(ParseError::ExpectedGotToken(
+                        ":".to_string(),
+                        t.clone(),
+                        loc.clone(),
+                    ))
+                }
+            }
+            let otherwise = self.parse_expr(1)?;
+            left = Expr::Conditional(Box::new(left), Box::new(then), Box::new(otherwise));
         }
 
         Ok(left)
@@ -224,62 +805,133 @@ impl Parser {
             .peek(0)
             .is_some_and(|Token(t, _)| t != &TokenType::RightBrace)
         {
-            let t = &self.peek(0).unwrap().0;
-            let loc = &self.peek(0).unwrap().1;
-            match t {
-                TokenType::Ident(_) => {
-                    if self
-                        .peek(1)
-                        .is_some_and(|Token(t, _)| t == &TokenType::Equals)
-                    {
-                        let out = self.parse_declaration(self.peek(0).unwrap().clone())?;
-                        block.push(out);
-                    } else if self
-                        .peek(1)
-                        .is_some_and(|Token(t, _)| t == &TokenType::LeftParen)
-                        && self.line_contains_equals()
-                    {
-                        let out = self.parse_function_declaration(self.peek(0).unwrap().clone())?;
-                        block.push(out);
-                    } else {
-                        let out = self.parse_print()?;
-                        block.push(out);
-                    }
-                }
-                TokenType::Keyword(keyword) => {
-                    if keyword.as_str() != "from" {
-                        return Err(ParseError::UnexpectedKeyword(
-                            keyword.to_string(),
-                            loc.clone(),
-                        ));
-                    }
-                    let out = self.parse_from_block()?;
-                    block.push(out);
-                }
-                TokenType::FloatLiteral(_) => {
-                    let out = self.parse_print()?;
-                    block.push(out);
-                }
-                TokenType::Comment => {
-                    while self.peek(0).is_some() && &self.peek(0).unwrap().0 != &TokenType::Newline
-                    {
-                        self.consume()?;
-                    }
+            if let Some(parsed) = self.parse_statement()? {
+                block.push(parsed);
+            }
+        }
+        self.consume()?;
+
+        Ok(block)
+    }
+
+    /// Parses the statement at the current token. Returns [`None`] if only a statement
+    /// separator was consumed.
+    fn parse_statement(&mut self) -> ParseResult<Option<Parsed>> {
+        let Some(Token(token_type, loc)) = self.peek(0) else {
+            return Err(ParseError::EOF);
+        };
+        let token = self.peek(0).unwrap().clone();
+        let parsed = match token_type {
+            TokenType::Ident(_) => {
+                if self
+                    .peek(1)
+                    .is_some_and(|Token(t, _)| t == &TokenType::Equals)
+                {
+                    self.parse_declaration(token)?
+                } else if self
+                    .peek(1)
+                    .is_some_and(|Token(t, _)| t == &TokenType::LeftParen)
+                    && self.line_contains_equals()
+                {
+                    self.parse_function_declaration(token)?
+                } else {
+                    self.parse_print()?
                 }
-                TokenType::Newline => {
+            }
+            TokenType::Keyword(keyword) if !STATEMENT_KEYWORDS.contains(&keyword.as_str()) => {
+                self.keyword_as_ident(0);
+                return Ok(None);
+            }
+            TokenType::Keyword(keyword) => match keyword.as_str() {
+                "from" => self.parse_from_block()?,
+                "for" => self.parse_for_block()?,
+                "write" => self.parse_write()?,
+                "print" => self.parse_print_keyword()?,
+                "import" => self.parse_import()?,
+                "assert" => self.parse_assert()?,
+                _ => unreachable!("Internal error!"),
+            },
+            TokenType::LeftBracket => {
+                if self.line_contains_equals() {
+                    let left = self.parse_spanned_expr()?;
                     self.consume()?;
+                    let right = self.parse_spanned_expr()?;
+                    Parsed::Destructuring(left, right)
+                } else {
+                    self.parse_print()?
                 }
-                token => todo!("Handle: {:?} at {}", token, loc),
             }
+            TokenType::FloatLiteral(_) | TokenType::StringLiteral(_) | TokenType::LeftParen => {
+                self.parse_print()?
+            }
+            TokenType::Newline | TokenType::Semicolon => {
+                self.consume()?;
+                return Ok(None);
+            }
+            TokenType::LeftBrace => Parsed::Block(self.parse_block()?),
+            token => return Err(ParseError::UnexpectedToken(token.clone(), loc.clone())),
+        };
+
+        Ok(Some(parsed))
+    }
+
+    /// Parses the `for x in list` clause of a list comprehension, after its body, along with an
+    /// optional `if condition` filter.
+    fn parse_comprehension(&mut self, body: Expr) -> ParseResult<Expr> {
+        self.consume()?;
+        self.keyword_as_ident(0);
+        let t = self.consume()?;
+        let Token(TokenType::Ident(name), _) = t else {
+            return Err(ParseError::ExpectedGotToken(
+                "identifier".to_string(),
+                t.0.clone(),
+                t.1.clone(),
+            ));
+        };
+        let name = name.to_string();
+        if !self.peek_keyword("in") {
+            let t = self.consume()?;
+            return Err(ParseError::ExpectedGotToken(
+                "in".to_string(),
+                t.0.clone(),
+                t.1.clone(),
+            ));
         }
         self.consume()?;
+        let list = self.parse_expr(1)?;
+        let mut filter = None;
+        if self.peek_keyword("if") {
+            self.consume()?;
+            filter = Some(Box::new(self.parse_expr(1)?));
+        }
+        if let Some(Token(t, loc)) = self.peek(0) {
+            if !matches!(
+                t,
+                TokenType::RightBracket | TokenType::Newline | TokenType::Semicolon
+            ) {
+                return Err(ParseError::ExpectedGotToken(
+                    "]".to_string(),
+                    t.clone(),
+                    loc.clone(),
+                ));
+            }
+        }
 
-        Ok(block)
+        Ok(Expr::Comprehension(
+            Box::new(body),
+            name,
+            Box::new(list),
+            filter,
+        ))
     }
 
     fn parse_for_block(&mut self) -> ParseResult<Parsed> {
-        self.consume()?;
-        let ident = self.parse_expr(1, false)?;
+        let span = self.consume()?.1.clone();
+        // Parsed above the precedence of `in`, which separates the name from the list.
+        let ident = Spanned {
+            span: self.peek(0).ok_or(ParseError::EOF)?.1.clone(),
+            node: self.parse_expr(get_prec(&Operator::In) + 1)?,
+        };
         let t = self.consume()?;
         let Token(TokenType::Keyword(keyword), loc) = t else {
             return Err(ParseError::Expected("in".to_string(), t.1.clone()));
@@ -291,15 +943,43 @@ impl Parser {
                 loc.clone(),
             ));
         };
-        let list = self.parse_expr(1, false)?;
+        let list = self.parse_spanned_expr()?;
+        // The default step has no source of its own, so it points at the loop.
+        let mut step = Spanned {
+            node: Expr::FloatLiteral("1.0".to_string()),
+            span,
+        };
+        let mut index: Option<Spanned<Expr>> = None;
+        while let Some(Token(TokenType::Keyword(keyword), _)) = self.peek(0) {
+            if keyword.as_str() != "with" {
+                break;
+            }
+            self.consume()?;
+            let t = self.consume()?.clone();
+            match &t {
+                Token(TokenType::Keyword(keyword), _) if keyword.as_str() == "step" => {
+                    step = self.parse_spanned_expr()?;
+                }
+                Token(TokenType::Ident(name), _) if name.as_str() == "index" => {
+                    index = Some(self.parse_spanned_expr()?);
+                }
+                Token(t, loc) => {
+                    return Err(ParseError::ExpectedGotToken(
+                        "step or index".to_string(),
+                        t.clone(),
+                        loc.clone(),
+                    ));
+                }
+            }
+        }
         let block: Vec<Parsed> = self.parse_block()?;
 
-        Ok(Parsed::ForLoop(ident, list, block))
+        Ok(Parsed::ForLoop(ident, list, step, index, block))
     }
 
     fn parse_from_block(&mut self) -> ParseResult<Parsed> {
-        self.consume()?;
-        let min = self.parse_expr(1, false)?;
+        let span = self.consume()?.1.clone();
+        let min = self.parse_spanned_expr()?;
         let t = self.consume()?;
         let Token(TokenType::Keyword(keyword), loc) = t else {
             return Err(ParseError::Expected("to".to_string(), t.1.clone()));
@@ -311,7 +991,7 @@ impl Parser {
                 loc.clone(),
             ));
         };
-        let max = self.parse_expr(1, false)?;
+        let max = self.parse_spanned_expr()?;
         let t = self.consume()?;
         let Token(TokenType::Keyword(keyword), loc) = t else {
             return Err(ParseError::Expected("as".to_string(), t.1.clone()));
@@ -323,8 +1003,18 @@ impl Parser {
                 loc.clone(),
             ));
         };
-        let ident = self.parse_expr(1, false)?;
-        let mut step: Expr = Expr::FloatLiteral("1.0".to_string());
+        let ident = self.parse_spanned_expr()?;
+        if !matches!(ident.node, Expr::Ident(_)) {
+            return Err(ParseError::ExpectedGot(
+                "identifier".to_string(),
+                ident.to_string(),
+                ident.span.clone(),
+            ));
+        }
+        let mut step = Spanned {
+            node: Expr::FloatLiteral("1.0".to_string()),
+            span,
+        };
         let Some(t) = self.peek(0) else {
             return Err(ParseError::EOF);
         };
@@ -335,8 +1025,7 @@ impl Parser {
                 let t = self.consume()?;
                 if let Token(TokenType::Keyword(keyword), loc) = t {
                     if keyword.as_str() == "step" {
-                        step = self.parse_expr(1, false)?;
-                        self.consume()?;
+                        step = self.parse_spanned_expr()?;
                     } else {
                         return Err(ParseError::ExpectedGot(
                             "step".to_string(),
@@ -353,7 +1042,6 @@ impl Parser {
                 };
             }
         }
-        self.consume()?;
 
         let block: Vec<Parsed> = self.parse_block()?;
 
@@ -361,14 +1049,27 @@ impl Parser {
     }
 
     fn parse_declaration(&mut self, ident: Token) -> ParseResult<Parsed> {
+        let mut idents = vec![ident];
         self.consume()?;
         self.consume()?;
-        let expr = self.parse_expr(1, false)?;
-        Ok(Parsed::Declaration(ident, expr))
+        // `a = b = expr` binds the value to every name in the chain.
+        while self
+            .peek(1)
+            .is_some_and(|Token(t, _)| t == &TokenType::Equals)
+        {
+            self.keyword_as_ident(0);
+            if !matches!(self.peek(0), Some(Token(TokenType::Ident(_), _))) {
+                break;
+            }
+            idents.push(self.consume()?.clone());
+            self.consume()?;
+        }
+        let expr = self.parse_spanned_expr()?;
+        Ok(Parsed::Declaration(idents, expr))
     }
 
     fn parse_function_declaration(&mut self, ident: Token) -> ParseResult<Parsed> {
-        let mut parameters: Vec<Token> = Vec::new();
+        let mut parameters: Vec<Parameter> = Vec::new();
         self.consume()?;
         self.consume()?;
 
@@ -376,113 +1077,366 @@ impl Parser {
             .peek(0)
             .is_some_and(|Token(t, _)| t != &TokenType::RightParen)
         {
-            if let TokenType::Ident(_) = self.peek(0).unwrap().0 {
-                parameters.push(self.peek(0).unwrap().clone());
+            self.keyword_as_ident(0);
+            let token = self.consume()?.clone();
+            if let TokenType::Ident(_) = token.0 {
+                let mut annotation = None;
+                if self
+                    .peek(0)
+                    .is_some_and(|Token(t, _)| t == &TokenType::Colon)
+                {
+                    self.consume()?;
+                    let Token(t, loc) = self.consume()?;
+                    annotation = Some(match t {
+                        TokenType::Ident(name) if name == "float" => ParamType::Float,
+                        TokenType::Ident(name) if name == "list" => ParamType::List,
+                        _ => {
+                            return Err(ParseError::ExpectedGotToken(
+                                "float or list".to_string(),
+                                t.clone(),
+                                loc.clone(),
+                            ))
+                        }
+                    });
+                }
+                let mut default = None;
+                let mut rest = false;
+                match self.peek(0) {
+                    Some(Token(TokenType::Equals, _)) => {
+                        self.consume()?;
+                        default = Some(self.parse_expr(1)?);
+                    }
+                    Some(Token(TokenType::Ellipsis, _)) => {
+                        self.consume()?;
+                        rest = true;
+                        // The rest parameter has to be the last one.
+                        if let Some(Token(t, loc)) = self.peek(0) {
+                            if t != &TokenType::RightParen {
+                                return Err(ParseError::ExpectedGotToken(
+                                    ")".to_string(),
+                                    t.clone(),
+                                    loc.clone(),
+                                ));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                parameters.push(Parameter {
+                    name: token,
+                    annotation,
+                    default,
+                    rest,
+                });
+            } else if token.0 != TokenType::Comma {
+                return Err(ParseError::ExpectedGotToken(
+                    "identifier".to_string(),
+                    token.0,
+                    token.1,
+                ));
             }
-            self.consume()?;
         }
         self.consume()?;
         self.consume()?;
-        let expr = self.parse_expr(1, true)?;
+        let expr = self.parse_spanned_expr()?;
         Ok(Parsed::FunctionDecleration(ident, parameters, expr))
     }
 
     fn parse_print(&mut self) -> ParseResult<Parsed> {
-        let expr = self.parse_expr(1, false)?;
+        let expr = self.parse_spanned_expr()?;
         // println!("{:?}", expr);
 
         Ok(Parsed::PrintExpr(expr))
     }
 
+    fn parse_write(&mut self) -> ParseResult<Parsed> {
+        self.consume()?;
+        let expr = self.parse_spanned_expr()?;
+
+        Ok(Parsed::Write(expr))
+    }
+
+    fn parse_print_keyword(&mut self) -> ParseResult<Parsed> {
+        self.consume()?;
+        let mut exprs = vec![self.parse_spanned_expr()?];
+        while self
+            .peek(0)
+            .is_some_and(|Token(t, _)| t == &TokenType::Comma)
+        {
+            self.consume()?;
+            exprs.push(self.parse_spanned_expr()?);
+        }
+
+        Ok(Parsed::Print(exprs))
+    }
+
+    fn parse_assert(&mut self) -> ParseResult<Parsed> {
+        let keyword = self.consume()?.clone();
+        let expr = self.parse_spanned_expr()?;
+        let mut message = None;
+        if self
+            .peek(0)
+            .is_some_and(|Token(t, _)| t == &TokenType::Comma)
+        {
+            self.consume()?;
+            message = Some(self.parse_spanned_expr()?);
+        }
+
+        Ok(Parsed::Assert(keyword, expr, message))
+    }
+
+    fn parse_import(&mut self) -> ParseResult<Parsed> {
+        let keyword = self.consume()?.clone();
+        let Token(token_type, loc) = self.consume()?;
+        let TokenType::StringLiteral(path) = token_type else {
+            return Err(ParseError::ExpectedGotToken(
+                "string".to_string(),
+                token_type.clone(),
+                loc.clone(),
+            ));
+        };
+        let resolved = match Path::new(&loc.0).parent() {
+            Some(dir) => dir.join(path).to_string_lossy().to_string(),
+            None => path.to_string(),
+        };
+
+        Ok(Parsed::Import(keyword, path.to_string(), resolved))
+    }
+
     fn line_contains_equals(&self) -> bool {
-        for i in 0..self.tokens.len() - self.index - 1 {
-            if !self.peek(i).is_some() {
-                return false;
+        // Only an `=` outside of brackets, like in `f(x, y = 1) = x + y`, makes a declaration.
+        let mut depth = 0usize;
+        for Token(t, _) in &self.tokens[self.index..] {
+            match t {
+                TokenType::Newline | TokenType::Semicolon => return false,
+                TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket => depth = depth.saturating_sub(1),
+                TokenType::Equals if depth == 0 => return true,
+                _ => {}
             }
+        }
 
-            let t = &self.peek(i).unwrap().0;
+        false
+    }
 
-            if t == &TokenType::Newline {
-                return false;
-            } else if t == &TokenType::Equals {
-                return true;
+    pub fn parse(&mut self) -> Result<Vec<Parsed>, MathyError> {
+        while let Some(Token(_, loc)) = self.peek(0) {
+            let line = loc.2;
+            if let Some(parsed) = self.parse_statement()? {
+                self.parsed.push(parsed);
+                self.lines.push(line);
             }
         }
 
-        return false;
+        Ok(self.parsed.to_vec())
     }
 
-    pub fn parse(&mut self) -> ParseResult<Vec<Parsed>> {
-        while let Some(Token(token_type, loc)) = self.peek(0) {
-            let token = self.peek(0).unwrap().clone();
-            match token_type {
-                TokenType::Ident(_) => {
-                    if self
-                        .peek(1)
-                        .is_some_and(|Token(t, _)| t == &TokenType::Equals)
-                    {
-                        let out = self.parse_declaration(token)?;
-                        self.parsed.push(out);
-                    } else if self
-                        .peek(1)
-                        .is_some_and(|Token(t, _)| t == &TokenType::LeftParen)
-                        && self.line_contains_equals()
-                    {
-                        let out = self.parse_function_declaration(token)?;
-                        self.parsed.push(out);
-                    } else {
-                        let out = self.parse_print()?;
-                        self.parsed.push(out);
-                    }
-                }
-                TokenType::Keyword(keyword) => match keyword.as_str() {
-                    "from" => {
-                        let out = self.parse_from_block()?;
-                        self.parsed.push(out);
-                    }
-                    "for" => {
-                        let out = self.parse_for_block()?;
-                        self.parsed.push(out);
-                    }
-                    _ => {
-                        return Err(ParseError::ExpectedGot(
-                            "for".to_string(),
-                            keyword.clone(),
-                            loc.clone(),
-                        ))
-                    }
-                },
-                TokenType::LeftBracket => {
-                    if self.line_contains_equals() {
-                        let left = self.parse_expr(1, false)?;
-                        self.consume()?;
-                        let right = self.parse_expr(1, false)?;
-                        self.parsed.push(Parsed::Destructuring(left, right));
-                    } else {
-                        let out = self.parse_print()?;
-                        self.parsed.push(out);
-                    }
-                }
-                TokenType::FloatLiteral(_) | TokenType::LeftParen => {
-                    let out = self.parse_print()?;
-                    self.parsed.push(out);
-                }
-                TokenType::Comment => {
-                    while let Some(Token(TokenType::Newline, _)) = self.peek(0) {
-                        self.consume()?;
-                    }
-                }
-                TokenType::Newline => {
-                    self.consume()?;
-                }
-                TokenType::LeftBrace => {
-                    let block = self.parse_block()?;
-                    self.parsed.push(Parsed::Block(block));
-                }
-                token => todo!("Handle {:?} at {}", token, loc),
-            };
+    /// Returns the line each statement returned by [`Parser::parse`] starts on.
+    pub fn statement_lines(&self) -> &[u32] {
+        &self.lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `src` and returns the parse error it fails with.
+    fn parse_err(src: &str) -> ParseError {
+        match crate::parse(src) {
+            Err(MathyError::Parse(err)) => err,
+            out => panic!("expected a parse error, got {:?}", out),
         }
+    }
 
-        Ok(self.parsed.to_vec())
+    #[test]
+    fn from_loop_target_must_be_a_name() {
+        assert!(crate::parse("from 0 to 3 as x {\n}").is_ok());
+        assert!(matches!(
+            parse_err("from 0 to 3 as 5 {\n}"),
+            ParseError::ExpectedGot(expected, got, _) if expected == "identifier" && got == "5"
+        ));
+    }
+
+    #[test]
+    fn statement_keywords_are_reserved() {
+        assert!(crate::parse("step = 5\nin = 1\nstep + in").is_ok());
+        for src in ["for = 3", "x = for", "print = 1", "f(for) = 1"] {
+            assert!(crate::parse(src).is_err(), "{:?} parsed", src);
+        }
+    }
+
+    #[test]
+    fn blocks_accept_every_statement() {
+        let parsed = crate::parse(
+            "{\n    for y in [1, 2] {\n        y\n    }\n    {\n        [a, b] = [1, 2]\n    }\n    (1)\n}",
+        )
+        .unwrap();
+        let [Parsed::Block(block)] = &parsed[..] else {
+            panic!("expected a block, got {:?}", parsed);
+        };
+        assert!(matches!(
+            block[..],
+            [Parsed::ForLoop(..), Parsed::Block(_), Parsed::PrintExpr(_)]
+        ));
+    }
+
+    #[test]
+    fn spans_match_the_lexer_locations() {
+        let tokens = crate::tokenize("x = 1\n  42").unwrap();
+        let literal = tokens
+            .iter()
+            .find(|Token(token_type, _)| matches!(token_type, TokenType::FloatLiteral(_)))
+            .map(|Token(_, loc)| loc.clone())
+            .unwrap();
+        let parsed = Parser::new(tokens).parse().unwrap();
+        let (Parsed::Declaration(_, value), Parsed::PrintExpr(expr)) = (&parsed[0], &parsed[1])
+        else {
+            panic!(
+                "expected a declaration and a bare expression, got {:?}",
+                parsed
+            );
+        };
+        assert_eq!((value.span.1, value.span.2), (literal.1, literal.2));
+        assert_eq!((expr.span.1, expr.span.2), (3, 2));
+        assert_eq!(parsed[0].span().map(|loc| (loc.1, loc.2)), Some((1, 1)));
+    }
+
+    #[test]
+    fn double_star_is_the_power_operator() {
+        let parsed = crate::parse("2 ** 10").unwrap();
+        assert!(matches!(
+            &parsed[..],
+            [Parsed::PrintExpr(expr)] if matches!(expr.node, Expr::Expr(_, Operator::Pow, _))
+        ));
+        assert!(matches!(
+            parse_err("2 * *3"),
+            ParseError::UnexpectedToken(TokenType::Multi, TokenLocation(_, 5, 1))
+        ));
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert!(crate::parse("f(a, b,) = a + b\nf(1, 2,)\n[1, 2,]").is_ok());
+        assert_eq!(
+            crate::eval("pow(2, 3,)").unwrap(),
+            Some(crate::Data::Float(8.0))
+        );
+        assert!(crate::parse("f(1,,)").is_err());
+    }
+
+    #[test]
+    fn delimiters_must_match() {
+        assert!(matches!(
+            parse_err("(1 + 2]"),
+            ParseError::ExpectedGotToken(expected, TokenType::RightBracket, TokenLocation(_, 7, 1))
+                if expected == ")"
+        ));
+        assert!(matches!(
+            parse_err("[x for x in [1] )"),
+            ParseError::ExpectedGotToken(expected, TokenType::RightParen, _) if expected == "]"
+        ));
+    }
+
+    #[test]
+    fn unterminated_calls_point_at_their_opening_parenthesis() {
+        assert!(matches!(
+            parse_err("f(1, 2"),
+            ParseError::Expected(expected, TokenLocation(_, 2, 1)) if expected == ")"
+        ));
+    }
+
+    #[test]
+    fn stray_closing_tokens_start_no_statement() {
+        for (src, token) in [
+            (")", TokenType::RightParen),
+            ("}", TokenType::RightBrace),
+            (",", TokenType::Comma),
+        ] {
+            assert!(matches!(
+                parse_err(src),
+                ParseError::UnexpectedToken(t, TokenLocation(_, 1, 1)) if t == token
+            ));
+        }
+    }
+
+    /// Parses `src` and formats it back into source.
+    fn format(src: &str) -> String {
+        crate::parse(src)
+            .unwrap()
+            .iter()
+            .map(|parsed| parsed.to_source(0))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn formatting_is_canonical_and_stable() {
+        let src = "f(x:float,y=2,rest...)=x**y+sum( rest )\n\
+                   a=b=[1,2,]\n\
+                   from 3 to 0 as i with step -1 {\nprint i,a[0]\n}\n\
+                   xs=[x*2 for x in a if x>1]\n\
+                   0<=a[0]<2?map(a,x->x+1):-1";
+        let formatted = format(src);
+        assert_eq!(
+            formatted,
+            "f(x: float, y = 2, rest...) = x ^ y + sum(rest)\n\
+             a = b = [1, 2]\n\
+             from 3 to 0 as i with step -1 {\n    print i, a[0]\n}\n\
+             xs = [x * 2 for x in a if x > 1]\n\
+             0 <= a[0] < 2 ? map(a, x -> x + 1) : -1"
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn formatting_keeps_only_needed_parentheses() {
+        assert_eq!(
+            format("(1 + 2) * 3 - 4 / (5 - 6)"),
+            "(1 + 2) * 3 - 4 / (5 - 6)"
+        );
+        assert_eq!(format("(1 * 2) + 3"), "1 * 2 + 3");
+        assert_eq!(format("1 +\n    2 *\n    3"), "1 + 2 * 3");
+    }
+
+    #[test]
+    fn imports_start_at_their_keyword() {
+        let parsed = crate::parse("x = 1\n  import \"lib.mth\"").unwrap();
+        assert!(matches!(&parsed[1], Parsed::Import(_, path, _) if path == "lib.mth"));
+        assert_eq!(parsed[1].span().map(|loc| (loc.1, loc.2)), Some((3, 2)));
+    }
+
+    #[test]
+    fn rest_parameters_come_last() {
+        let parsed = crate::parse("f(x: list, y = 1, z...) = x").unwrap();
+        let [Parsed::FunctionDecleration(_, parameters, _)] = &parsed[..] else {
+            panic!("expected a function, got {:?}", parsed);
+        };
+        assert_eq!(parameters.len(), 3);
+        assert!(parameters[1].default.is_some() && !parameters[1].rest);
+        assert!(parameters[2].rest);
+        assert!(matches!(
+            parse_err("f(rest..., a) = a"),
+            ParseError::ExpectedGotToken(expected, TokenType::Comma, _) if expected == ")"
+        ));
+    }
+
+    #[test]
+    fn comparisons_and_bracketed_equals_are_not_declarations() {
+        let parsed = crate::parse("x = 1; x == 1; y <= 2; z = 3").unwrap();
+        assert!(matches!(
+            parsed[..],
+            [
+                Parsed::Declaration(..),
+                Parsed::PrintExpr(_),
+                Parsed::PrintExpr(_),
+                Parsed::Declaration(..)
+            ]
+        ));
+        // Parsed as an expression, so the `=` is rejected where it stands.
+        assert!(matches!(
+            parse_err("f(1, [y = 2]); x = 1"),
+            ParseError::UnexpectedToken(TokenType::Equals, TokenLocation(_, 9, 1))
+        ));
     }
 }
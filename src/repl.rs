@@ -0,0 +1,183 @@
+use crate::error::MathyError;
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::util::error;
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::{
+    env,
+    fs::{read_to_string, write},
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+/// The name of the history file, stored in the user's home directory.
+const HISTORY_FILE: &str = ".mathy_history";
+/// The maximum number of entries kept in the history file.
+const HISTORY_LIMIT: usize = 1000;
+
+/// Returns the default history file path, or [`None`] if no home directory is set.
+pub fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Loads the history entries stored at `path`, one per line. A missing file is an empty history.
+pub fn load_history(path: &Path) -> Result<Vec<String>> {
+    match read_to_string(path) {
+        Ok(content) => Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Saves the last [`HISTORY_LIMIT`] entries to `path`, one per line.
+pub fn save_history(path: &Path, entries: &[String]) -> Result<()> {
+    let start = entries.len().saturating_sub(HISTORY_LIMIT);
+    let mut content = String::new();
+    for entry in &entries[start..] {
+        content.push_str(entry);
+        content.push('\n');
+    }
+
+    write(path, content)
+}
+
+/// Returns how many more braces were opened than closed in `source`.
+fn brace_depth(source: &str) -> i64 {
+    source.chars().fold(0, |depth, c| match c {
+        '{' => depth + 1,
+        '}' => depth - 1,
+        _ => depth,
+    })
+}
+
+pub struct Repl {
+    interpreter: Interpreter,
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+impl Repl {
+    /// Creates a REPL that persists its history to `history_path`, if given.
+    pub fn new(history_path: Option<PathBuf>) -> Self {
+        Self {
+            interpreter: Interpreter::new(Vec::new()),
+            history: Vec::new(),
+            history_path,
+        }
+    }
+
+    /// Sets the maximum depth of nested user-defined function calls.
+    pub fn set_recursion_limit(&mut self, recursion_limit: usize) {
+        self.interpreter.set_recursion_limit(recursion_limit);
+    }
+
+    fn evaluate(&mut self, source: String) -> std::result::Result<(), MathyError> {
+        let tokens = Lexer::new("<repl>".to_string(), source).tokenize()?;
+        let parsed = Parser::new(tokens).parse()?;
+        self.interpreter.execute(parsed)
+    }
+
+    /// Handles a REPL command such as `:vars`.
+    fn command(&mut self, command: &str) {
+        match command {
+            ":vars" => println!("{}", self.interpreter.variable_names().join(", ")),
+            ":funcs" => println!("{}", self.interpreter.function_names().join(", ")),
+            ":builtins" => println!("{}", self.interpreter.available_names().join(", ")),
+            ":reset" => self.interpreter.reset(),
+            _ => eprintln!("Unknown command: {:?}", command),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        let mut editor = DefaultEditor::new().map_err(|err| error!(Other, "{}", err))?;
+
+        if let Some(path) = &self.history_path {
+            self.history = load_history(path)?;
+        }
+        for entry in &self.history {
+            let _ = editor.add_history_entry(entry.as_str());
+        }
+
+        let mut buf = String::new();
+        loop {
+            let prompt = if buf.is_empty() { ">> " } else { ".. " };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        self.history.push(line.clone());
+                    }
+
+                    if buf.is_empty() && line.trim().starts_with(':') {
+                        self.command(line.trim());
+                        continue;
+                    }
+
+                    buf.push_str(&line);
+                    buf.push('\n');
+                    if brace_depth(&buf) > 0 {
+                        continue;
+                    }
+
+                    let source = std::mem::take(&mut buf);
+                    if let Err(err) = self.evaluate(source) {
+                        eprintln!("{}", err);
+                    }
+                }
+                Err(ReadlineError::Interrupted) => buf.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(error!(Other, "{}", err)),
+            }
+        }
+
+        if let Some(path) = &self.history_path {
+            save_history(path, &self.history)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::InterpreterError;
+
+    #[test]
+    fn history_round_trip() {
+        let path = env::temp_dir().join(format!("mathy-history-{}", std::process::id()));
+        let entries: Vec<String> = (0..HISTORY_LIMIT + 5)
+            .map(|i| format!("x{} = {}", i, i))
+            .collect();
+        save_history(&path, &entries).unwrap();
+        let loaded = load_history(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Only the last entries are kept.
+        assert_eq!(loaded, entries[5..]);
+        assert!(load_history(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reset_clears_declarations_but_keeps_built_ins() {
+        let mut repl = Repl::new(None);
+        repl.evaluate("x = 1\nf(a) = a * 2".to_string()).unwrap();
+        repl.command(":reset");
+        assert!(repl.interpreter.variable_names().is_empty());
+        assert!(repl.interpreter.function_names().is_empty());
+
+        assert!(matches!(
+            repl.evaluate("y = x".to_string()),
+            Err(MathyError::Interpreter(err))
+                if matches!(err.inner(), InterpreterError::UndefinedVariable(name) if name == "x")
+        ));
+        repl.evaluate("x = sqrt(4)".to_string()).unwrap();
+        assert_eq!(repl.interpreter.variable_names(), ["x"]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// Writes `src` to a file named `name` in a temporary directory shared by the tests.
+fn write(name: &str, src: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mathy-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, src).unwrap();
+    path
+}
+
+/// Writes `src` to a file named `name` in a temporary directory and runs mathy on it with
+/// `args`.
+fn run(name: &str, src: &str, args: &[&str]) -> Output {
+    let path = write(name, src);
+    Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn max_steps_stops_huge_fills() {
+    let output = run("fill.mth", "fill(0, 10 ^ 11)\n", &["--max-steps", "100"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Step limit of 100 exceeded at "));
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(run("ok.mth", "1 + 2\n", &[]).status.code(), Some(0));
+    assert_eq!(run("runtime.mth", "y + 1\n", &[]).status.code(), Some(1));
+    assert_eq!(run("syntax.mth", "(1 + 2\n", &[]).status.code(), Some(2));
+    assert_eq!(
+        run("flag.mth", "1\n", &["--output", "xml"]).status.code(),
+        Some(64)
+    );
+    let missing = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg("does-not-exist.mth")
+        .output()
+        .unwrap();
+    assert_eq!(missing.status.code(), Some(64));
+}
+
+#[test]
+fn fmt_refuses_comments_and_keeps_blank_lines() {
+    let output = run("comments.mth", "x = 1 # one\nx\n", &["--fmt"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--fmt can't keep comments"));
+    assert!(output.stdout.is_empty());
+
+    let output = run("blank.mth", "x=1\ny=2\n\n\nx+y\n", &["--fmt"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "x = 1\ny = 2\n\nx + y\n"
+    );
+}
+
+#[test]
+fn no_color_prints_plain_errors() {
+    let output = run("color.mth", "y + 1\n", &["--no-color"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Undefined variable: \"y\""));
+    assert!(!stderr.contains('\x1b'));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .env("NO_COLOR", "1")
+        .arg(write("color_env.mth", "y + 1\n"))
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&output.stderr).contains('\x1b'));
+}
+
+#[test]
+fn json_input_defines_variables() {
+    let variables = write("vars.json", r#"{"xs": [1, 2, 3], "k": 2}"#);
+    let output = run(
+        "scale.mth",
+        "xs * k\n",
+        &["--json-input", variables.to_str().unwrap()],
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[2, 4, 6]\n");
+
+    let invalid = write("invalid.json", r#"{"xs": "text"}"#);
+    let output = run(
+        "scale.mth",
+        "xs\n",
+        &["--json-input", invalid.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn dump_ast_prints_the_program_without_running_it() {
+    let path = write("ast.mth", "print 1 + 2\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg("--dump-ast")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    let expected = r#"[
+    Print(
+        [
+            Spanned {
+                node: Expr(
+                    FloatLiteral(
+                        "1.0",
+                    ),
+                    Plus,
+                    FloatLiteral(
+                        "2.0",
+                    ),
+                ),
+                span: TokenLocation(
+                    "PATH",
+                    7,
+                    1,
+                ),
+            },
+        ],
+    ),
+]
+"#
+    .replace("PATH", &path.display().to_string());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+}
+
+#[test]
+fn dump_tokens_prints_one_token_per_line() {
+    let path = write("tokens.mth", "x = 1\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .args(["--dump-tokens", "--check"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let expected = [
+        "1:1: Ident(\"x\")",
+        "1:3: Equals",
+        "1:5: FloatLiteral(\"1.0\")",
+        "1:6: Newline",
+    ]
+    .map(|line| format!("{}:{}\n", path.display(), line))
+    .concat();
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+}
+
+#[test]
+fn json_output() {
+    let output = run(
+        "json.mth",
+        "[1, 2] * 2\nprint \"a\", 3\n",
+        &["--output", "json"],
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[2,4]\n\"a\" 3\n");
+}
+
+#[test]
+fn recursion_is_limited_to_a_depth_of_1000() {
+    let output = run(
+        "deep.mth",
+        "f(x) = x <= 0 ? 0 : 1 + f(x - 1)\nf(999)\n",
+        &[],
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "999\n");
+
+    let output = run("runaway.mth", "f(x) = f(x)\nf(1)\n", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Recursion limit reached in function \"f\""));
+}
+
+#[test]
+fn imports() {
+    write("lib.mth", "double(x) = x * 2\n");
+    let output = run("main.mth", "import \"lib.mth\"\ndouble(21)\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+}
+
+#[test]
+fn importing_the_main_file_back_is_a_cycle() {
+    write("cycle_b.mth", "import \"cycle_a.mth\"\n");
+    let main = write("cycle_a.mth", "import \"cycle_b.mth\"\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg(&main)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "Cyclic import of {:?}",
+        main.display().to_string()
+    )));
+}
+
+#[test]
+fn version() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg("--version")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("mathy {}\n", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn time_reports_each_stage_on_stderr() {
+    let output = run("time.mth", "1 + 2\n", &["--time"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stages: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split(": ").next().unwrap())
+        .collect();
+    assert_eq!(stages, ["lexing", "parsing", "interpreting"]);
+}
+
+#[test]
+fn failed_assertions_stop_the_program() {
+    let output = run("assert_ok.mth", "assert 1 < 2\nprint 1\n", &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+
+    let output = run("assert_fail.mth", "assert 2 < 1, \"no\"\nprint 1\n", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Assertion failed at "));
+}
+
+#[test]
+fn dash_reads_the_program_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"print 1 + 2\ny\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<stdin>:2:1"));
+}
+
+#[test]
+fn warn_unused_reports_only_unused_names() {
+    let path = write("unused.mth", "x = 1\ny = 2\nprint y\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_mathy"))
+        .arg("--warn-unused")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("{}:1:1: warning: unused variable \"x\"\n", path.display())
+    );
+}